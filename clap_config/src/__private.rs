@@ -0,0 +1,1153 @@
+/*!
+Helpers called by the code generated by the `ClapConfig` derive macro.
+
+Nothing in here is part of the public API, and it may change in any release.
+*/
+
+use clap::error::ContextKind;
+use clap::error::ContextValue;
+use clap::error::ErrorKind;
+use clap::builder::ArgPredicate;
+use clap::builder::OsStr;
+use clap::builder::Resettable;
+use clap::parser::ValueSource;
+use clap::Arg;
+use clap::ArgAction;
+use clap::ArgMatches;
+use clap::Command;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use crate::ExampleFormat;
+use crate::Lockable;
+use crate::sources::Origin;
+use crate::Source;
+use crate::Sources;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use crate::aliases::Aliases;
+pub use crate::config_args::ConfigArgs;
+pub use crate::config_file::MergeConfig;
+pub use crate::example::ExampleSection;
+pub use crate::strict::ConfigKeys;
+pub use crate::strict::OtherKeys;
+pub use crate::strict::Sections;
+pub use serde_json::Value;
+
+/**
+Build the `clap::Command` for a struct that derives `clap::Args` (or `clap::Parser`).
+
+The command is built so that its args render the same way as in clap's own errors.
+*/
+pub fn command<T: clap::Args>() -> Command {
+    let mut cmd = T::augment_args(Command::new(std::any::type_name::<T>()));
+    cmd.build();
+    cmd
+}
+
+/**
+Build the `clap::Command` for a struct, see [`command`], named after the subcommand `name` if the
+struct holds the args of a subcommand, so that errors name the subcommand rather than the type.
+*/
+pub fn subcommand_command<T: clap::Args>(name: Option<&str>) -> Command {
+    match name {
+        Some(name) => {
+            let mut cmd = T::augment_args(Command::new(name.to_owned()));
+            cmd.build();
+            cmd
+        }
+        None => command::<T>(),
+    }
+}
+
+/// Parse a single config value with the value parser of the arg with id `id`.
+pub fn parse_config_value<T>(
+    cmd: &Command,
+    id: &str,
+    key: &str,
+    raw: String,
+) -> Result<T, clap::Error>
+where
+    T: Any + Clone + Send + Sync + 'static,
+{
+    let mut values = parse_config_values(cmd, id, key, vec![raw])?;
+    Ok(values.remove(0))
+}
+
+/**
+Parse config values with the value parser of the arg with id `id`, so that the config accepts
+exactly the same values as the command line.
+
+`key` is the name of the value in the config, which is used in error messages.
+*/
+pub fn parse_config_values<T>(
+    cmd: &Command,
+    id: &str,
+    key: &str,
+    raw: Vec<String>,
+) -> Result<Vec<T>, clap::Error>
+where
+    T: Any + Clone + Send + Sync + 'static,
+{
+    let arg = cmd
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .unwrap_or_else(|| panic!("Expected the command to have an arg with id '{id}'."));
+
+    // A command with a single positional arg that only runs the value parser, so we don't pick up
+    // any of the other arg settings (required, conflicts, etc.) that apply to the command line.
+    let mut parser_cmd = Command::new(cmd.get_name().to_owned())
+        .no_binary_name(true)
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .arg(
+            Arg::new(id.to_owned())
+                .value_parser(arg.get_value_parser().clone())
+                .ignore_case(arg.is_ignore_case_set())
+                .allow_hyphen_values(true)
+                .num_args(0..)
+                .action(ArgAction::Append),
+        );
+
+    let mut matches = parser_cmd.try_get_matches_from_mut(raw).map_err(|mut e| {
+        e.insert(
+            ContextKind::InvalidArg,
+            ContextValue::String(format!("config key {key}")),
+        );
+        // The usage of our internal command isn't useful to the user.
+        e.insert(ContextKind::Usage, ContextValue::None);
+        e
+    })?;
+
+    Ok(matches
+        .remove_many::<T>(id)
+        .map(|values| values.collect())
+        .unwrap_or_default())
+}
+
+/// The raw default values of the arg with id `id`, if it has any.
+pub fn default_values(cmd: &Command, id: &str) -> Option<Vec<String>> {
+    let arg = cmd
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .unwrap_or_else(|| panic!("Expected the command to have an arg with id '{id}'."));
+    let values: Vec<_> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
+
+/// The default values of the arg with id `id` parsed with its value parser, if it has any.
+pub fn parse_default_values<T>(cmd: &Command, id: &str) -> Option<Vec<T>>
+where
+    T: Any + Clone + Send + Sync + 'static,
+{
+    let values = default_values(cmd, id)?;
+    Some(
+        parse_config_values(cmd, id, id, values)
+            .unwrap_or_else(|e| panic!("Expected the default value of '{id}' to be valid: {e}")),
+    )
+}
+
+/**
+Parse `key: value` config map entries with the value parser of the arg with id `id`, by passing them
+as `key=value`, which is how the same entries are passed on the command line.
+*/
+pub fn parse_config_map(
+    cmd: &Command,
+    id: &str,
+    key: &str,
+    map: BTreeMap<String, String>,
+) -> Result<Vec<(String, String)>, clap::Error> {
+    parse_config_values(
+        cmd,
+        id,
+        key,
+        map.into_iter().map(|(k, v)| format!("{k}={v}")).collect(),
+    )
+}
+
+/**
+A config value that will be passed to a clap value parser.
+
+Accepts any scalar, so that e.g. `port = 80` in TOML or JSON works the same way as `--port 80` on
+the command line.
+*/
+struct RawValue(String);
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawValueVisitor;
+
+        impl serde::de::Visitor<'_> for RawValueVisitor {
+            type Value = RawValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string, number, or boolean")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<RawValue, E> {
+                Ok(RawValue(v.to_owned()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> Result<RawValue, E> {
+                Ok(RawValue(v))
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<RawValue, E> {
+                Ok(RawValue(v.to_string()))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<RawValue, E> {
+                Ok(RawValue(v.to_string()))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<RawValue, E> {
+                Ok(RawValue(v.to_string()))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<RawValue, E> {
+                Ok(RawValue(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(RawValueVisitor)
+    }
+}
+
+/// Deserialize a config value that will be passed to a clap value parser.
+pub fn deserialize_raw_value<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<RawValue>::deserialize(deserializer)?.map(|raw| raw.0))
+}
+
+/// Deserialize a list of config values that will be passed to a clap value parser.
+pub fn deserialize_raw_values<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    Ok(Option::<Vec<RawValue>>::deserialize(deserializer)?
+        .map(|raw| raw.into_iter().map(|raw| raw.0).collect()))
+}
+
+/// Deserialize a lockable config value that will be passed to a clap value parser.
+pub fn deserialize_lockable_raw_value<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Lockable<String>>, D::Error> {
+    Ok(Option::<Lockable<RawValue>>::deserialize(deserializer)?.map(|l| l.map(|raw| raw.0)))
+}
+
+/// Deserialize a lockable list of config values that will be passed to a clap value parser.
+pub fn deserialize_lockable_raw_values<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Lockable<Vec<String>>>, D::Error> {
+    Ok(
+        Option::<Lockable<Vec<RawValue>>>::deserialize(deserializer)?
+            .map(|l| l.map(|raw| raw.into_iter().map(|raw| raw.0).collect())),
+    )
+}
+
+/**
+Deserialize a nullable config value, where the field has `#[serde(default)]` so that a missing key
+is `None`, and an explicit `null` is `Some(None)`.
+*/
+pub fn deserialize_nullable<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error> {
+    Ok(Some(Option::<T>::deserialize(deserializer)?))
+}
+
+/// Deserialize a nullable config value that will be passed to a clap value parser, see
+/// [`deserialize_nullable`].
+pub fn deserialize_nullable_raw_value<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<String>>, D::Error> {
+    Ok(Some(deserialize_raw_value(deserializer)?))
+}
+
+/// Deserialize a nullable list of config values that will be passed to a clap value parser, see
+/// [`deserialize_nullable`].
+pub fn deserialize_nullable_raw_values<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<Vec<String>>>, D::Error> {
+    Ok(Some(deserialize_raw_values(deserializer)?))
+}
+
+/// Split a lockable config value into the value and whether it is locked.
+pub fn unlock<T>(lockable: Option<Lockable<T>>) -> (Option<T>, bool) {
+    match lockable {
+        Some(lockable) => {
+            let locked = lockable.is_locked();
+            (Some(lockable.into_value()), locked)
+        }
+        None => (None, false),
+    }
+}
+
+
+/// Error for a required arg that wasn't set on the command line or in the config.
+pub fn missing_required_error(key: &str) -> clap::Error {
+    clap::Error::raw(
+        ErrorKind::MissingRequiredArgument,
+        format!("Required arg '{key}' not provided in args or config.\n"),
+    )
+}
+
+/// Error for a required subcommand that wasn't given on the command line or chosen by the
+/// config, see `#[clap_config(default_command_key)]`.
+pub fn missing_subcommand_error(cmd: &Command) -> clap::Error {
+    let mut err = clap::Error::new(ErrorKind::MissingSubcommand).with_cmd(cmd);
+    err.insert(
+        ContextKind::InvalidSubcommand,
+        ContextValue::String(cmd.get_name().to_owned()),
+    );
+    err
+}
+
+/**
+The name and matches of the subcommand invoked on the command line, removed from `matches`, or
+else of the subcommand chosen by the config, as `default`: its config key and name.
+*/
+pub fn take_invoked_subcommand(
+    cmd: &Command,
+    matches: &mut ArgMatches,
+    default: Option<(&str, &str)>,
+) -> Result<Option<(String, ArgMatches)>, clap::Error> {
+    match matches.remove_subcommand() {
+        Some(subcommand) => Ok(Some(subcommand)),
+        None => default
+            .map(|(key, name)| default_subcommand(cmd, key, name))
+            .transpose(),
+    }
+}
+
+/// Like [`take_invoked_subcommand`], but leaves `matches` untouched.
+pub fn invoked_subcommand(
+    cmd: &Command,
+    matches: &ArgMatches,
+    default: Option<(&str, &str)>,
+) -> Result<Option<(String, ArgMatches)>, clap::Error> {
+    match matches.subcommand() {
+        Some((name, matches)) => Ok(Some((name.to_owned(), matches.clone()))),
+        None => default
+            .map(|(key, name)| default_subcommand(cmd, key, name))
+            .transpose(),
+    }
+}
+
+/**
+The name and matches of the subcommand `name` of `cmd`, as if it was invoked without any args.
+`key` is the config key it was read from, which is used in error messages.
+
+`name` can also be spelled like the config key of the subcommand section, e.g. `serve_all` for
+the `serve-all` subcommand.
+
+Its required args, groups and subcommands are made optional, so they can be set in its config
+section and are checked after merging instead.
+*/
+fn default_subcommand(
+    cmd: &Command,
+    key: &str,
+    name: &str,
+) -> Result<(String, ArgMatches), clap::Error> {
+    let subcommand = cmd.find_subcommand(name).or_else(|| {
+        cmd.get_subcommands()
+            .find(|subcommand| subcommand.get_name().replace('-', "_") == name)
+    });
+    let Some(subcommand) = subcommand else {
+        let mut message =
+            format!("invalid value '{name}' for config key {key}: no such subcommand\n");
+        let names = cmd.get_subcommands().map(Command::get_name);
+        if let Some(similar) = crate::strict::similar(name, names) {
+            message.push_str(&format!("\n  tip: a similar subcommand exists: '{similar}'\n"));
+        }
+        return Err(clap::Error::raw(ErrorKind::InvalidSubcommand, message));
+    };
+    let subcommand = relax_groups(subcommand.clone())
+        .subcommand_required(false)
+        .arg_required_else_help(false)
+        .mut_args(|arg| arg.required(false));
+    let matches = subcommand
+        .clone()
+        .try_get_matches_from([subcommand.get_name()])?;
+    Ok((subcommand.get_name().to_owned(), matches))
+}
+
+/// An arg that was set on the command line, by an env var, or in the config.
+pub struct PresentArg {
+    /// The clap id of the arg.
+    pub id: &'static str,
+    /// The config key the value was read from, or `None` if it came from clap.
+    pub config_key: Option<&'static str>,
+}
+
+impl PresentArg {
+    /// How to refer to this arg in error messages.
+    fn display(&self, cmd: &Command) -> String {
+        match self.config_key {
+            Some(key) => format!("config key {key}"),
+            None => display_arg(cmd, self.id),
+        }
+    }
+}
+
+/// Render the arg with id `id` the same way clap does in its errors, e.g. `--format <FORMAT>`.
+fn display_arg(cmd: &Command, id: &str) -> String {
+    if let Some(arg) = cmd.get_arguments().find(|arg| arg.get_id() == id) {
+        arg.to_string()
+    } else {
+        format!("<{}>", group_members(cmd, id).join("|"))
+    }
+}
+
+/// The display names of all the args in the group with id `id`, including nested groups.
+fn group_members(cmd: &Command, id: &str) -> Vec<String> {
+    let Some(group) = cmd.get_groups().find(|group| group.get_id() == id) else {
+        return vec![];
+    };
+    group
+        .get_args()
+        .flat_map(|member| {
+            if cmd.get_arguments().any(|arg| arg.get_id() == member) {
+                vec![display_arg(cmd, member.as_str())]
+            } else {
+                group_members(cmd, member.as_str())
+            }
+        })
+        .collect()
+}
+
+/// Whether the arg or group with id `id` has a present arg.
+fn is_present(cmd: &Command, present: &[PresentArg], id: &str) -> bool {
+    present.iter().any(|p| p.id == id)
+        || cmd
+            .get_groups()
+            .find(|group| group.get_id() == id)
+            .is_some_and(|group| {
+                group
+                    .get_args()
+                    .any(|member| is_present(cmd, present, member.as_str()))
+            })
+}
+
+fn conflict_error(cmd: &Command, invalid: &PresentArg, prior: &PresentArg) -> clap::Error {
+    let mut e = clap::Error::new(ErrorKind::ArgumentConflict).with_cmd(cmd);
+    e.insert(
+        ContextKind::InvalidArg,
+        ContextValue::String(invalid.display(cmd)),
+    );
+    e.insert(
+        ContextKind::PriorArg,
+        ContextValue::String(prior.display(cmd)),
+    );
+    e.insert(ContextKind::Usage, ContextValue::None);
+    e
+}
+
+fn missing_error(cmd: &Command, missing: String) -> clap::Error {
+    let mut e = clap::Error::new(ErrorKind::MissingRequiredArgument).with_cmd(cmd);
+    e.insert(
+        ContextKind::InvalidArg,
+        ContextValue::Strings(vec![missing]),
+    );
+    e.insert(ContextKind::Usage, ContextValue::None);
+    e
+}
+
+/**
+Check the `conflicts_with`, `exclusive`, `requires`, and `ArgGroup` rules of `cmd` against the
+merged values, treating values from the config as present.
+
+Clap only checks these rules against the command line, so e.g. `--json` on the command line and
+`format: text` in the config would otherwise both be accepted even if they conflict.
+
+`requires` holds the `(arg id, required id)` pairs from the `requires` attributes, as clap doesn't
+expose them on the `Command`.
+*/
+pub fn validate_relationships(
+    cmd: &Command,
+    present: &[PresentArg],
+    requires: &[(&str, clap::Id)],
+) -> Result<(), clap::Error> {
+    for p in present {
+        let Some(arg) = cmd.get_arguments().find(|arg| arg.get_id() == p.id) else {
+            continue;
+        };
+        if arg.is_exclusive_set()
+            && let Some(other) = present.iter().find(|other| other.id != p.id)
+        {
+            return Err(conflict_error(cmd, p, other));
+        }
+        let conflicts = cmd.get_arg_conflicts_with(arg);
+        if let Some(other) = present
+            .iter()
+            .find(|other| conflicts.iter().any(|c| c.get_id() == other.id))
+        {
+            return Err(conflict_error(cmd, p, other));
+        }
+    }
+
+    for (id, required) in requires {
+        if present.iter().any(|p| p.id == *id) && !is_present(cmd, present, required.as_str()) {
+            return Err(missing_error(cmd, display_arg(cmd, required.as_str())));
+        }
+    }
+
+    for group in cmd.get_groups() {
+        let members: Vec<&PresentArg> = present
+            .iter()
+            .filter(|p| is_present(cmd, std::slice::from_ref(p), group.get_id().as_str()))
+            .collect();
+        if members.is_empty() && group.is_required_set() {
+            return Err(missing_error(
+                cmd,
+                display_arg(cmd, group.get_id().as_str()),
+            ));
+        }
+        if let [first, second, ..] = members.as_slice()
+            && !group.clone().is_multiple()
+        {
+            return Err(conflict_error(cmd, second, first));
+        }
+    }
+
+    Ok(())
+}
+
+/**
+Make the groups of `cmd` optional, so clap doesn't reject a command line where the group is only
+set in the config. They are checked again by [`validate_relationships`] after merging.
+*/
+pub fn relax_groups(mut cmd: Command) -> Command {
+    let required: Vec<clap::Id> = cmd
+        .get_groups()
+        .filter(|group| group.is_required_set())
+        .map(|group| group.get_id().clone())
+        .collect();
+    for id in required {
+        cmd = cmd.mut_group(id, |group| group.required(false));
+    }
+    cmd
+}
+
+/**
+Make the args of `cmd` with the ids `ids` optional, so clap doesn't reject a command line where a
+required arg is only set in the config. The merge reports them with [`missing_required_error`] if
+they're set in neither.
+*/
+pub fn relax_required(mut cmd: Command, ids: &[&str]) -> Command {
+    for id in ids {
+        if cmd.get_arguments().any(|arg| arg.get_id() == id) {
+            cmd = cmd.mut_arg(*id, |arg| arg.required(false));
+        }
+    }
+    cmd
+}
+
+/**
+The raw strings for a config value, as they would be passed on the command line, e.g. `true` for
+`true`, `8` for `8`, and `key=value` for each entry of a map.
+*/
+pub fn raw_config_values<T: Serialize>(value: &T) -> Vec<String> {
+    fn raw(value: serde_json::Value) -> Vec<String> {
+        match value {
+            serde_json::Value::Null => vec![],
+            serde_json::Value::String(s) => vec![s],
+            serde_json::Value::Array(values) => values.into_iter().flat_map(raw).collect(),
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .flat_map(|(k, v)| raw(v).into_iter().map(move |v| format!("{k}={v}")))
+                .collect(),
+            other => vec![other.to_string()],
+        }
+    }
+    serde_json::to_value(value).map(raw).unwrap_or_default()
+}
+
+/// The raw value of a `value_enum` field, i.e. the name of its possible value.
+pub fn value_enum_name<T: clap::ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+/**
+A value of a field that's stored as a string in the config, to convert it back to that string with
+`(&&&ToRaw(value)).to_raw()` when converting a struct back into its config.
+
+The conversion is picked by the traits the type implements, without requiring any of them: its
+`ValueEnum` name, else its `Display`, else its `Serialize` if that's a single scalar, else `None`
+and the field is left out. This only works where the type is known, i.e. in the generated code.
+*/
+pub struct ToRaw<'a, T>(pub &'a T);
+
+/// Convert a value to its raw config string with `ValueEnum`, see [`ToRaw`].
+pub trait ValueEnumToRaw {
+    fn to_raw(&self) -> Option<String>;
+}
+
+impl<T: clap::ValueEnum> ValueEnumToRaw for &&ToRaw<'_, T> {
+    fn to_raw(&self) -> Option<String> {
+        Some(value_enum_name(self.0))
+    }
+}
+
+/// Convert a value to its raw config string with `Display`, see [`ToRaw`].
+pub trait DisplayToRaw {
+    fn to_raw(&self) -> Option<String>;
+}
+
+impl<T: fmt::Display> DisplayToRaw for &&&ToRaw<'_, T> {
+    fn to_raw(&self) -> Option<String> {
+        Some(self.0.to_string())
+    }
+}
+
+/// Convert a value to its raw config string with `Serialize`, see [`ToRaw`].
+pub trait SerializeToRaw {
+    fn to_raw(&self) -> Option<String>;
+}
+
+impl<T: Serialize> SerializeToRaw for &ToRaw<'_, T> {
+    fn to_raw(&self) -> Option<String> {
+        serialized_scalar(self.0)
+    }
+}
+
+/// The fallback for values that can't be converted to a raw config string, see [`ToRaw`].
+pub trait NoToRaw {
+    fn to_raw(&self) -> Option<String>;
+}
+
+impl<T> NoToRaw for ToRaw<'_, T> {
+    fn to_raw(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The serialized value as a string, if it's a string, number, or boolean.
+fn serialized_scalar<T: Serialize>(value: &T) -> Option<String> {
+    match serde_json::to_value(value).ok()? {
+        serde_json::Value::String(s) => Some(s),
+        value @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_)) => {
+            Some(value.to_string())
+        }
+        _ => None,
+    }
+}
+
+/**
+How the raw command line value `raw` of the arg with id `id` is spelt in the config, for a field
+that's deserialized with serde instead of parsed, e.g. `AlwaysOn` for `always-on` for a `ValueEnum`
+without `value_enum`. Falls back to `raw` if the value can't be converted.
+*/
+pub fn config_spelling<T>(cmd: &Command, id: &str, raw: &str) -> String
+where
+    T: Any + Clone + Send + Sync + Serialize + 'static,
+{
+    parse_config_value::<T>(cmd, id, id, raw.to_owned())
+        .ok()
+        .and_then(|value| serialized_scalar(&value))
+        .unwrap_or_else(|| raw.to_owned())
+}
+
+/**
+Resolve the `default_value_if` and `default_value_ifs` conditions of an arg against the merged
+values, returning the raw default of the first condition that matches, which is `None` for a
+condition that resets the default. Returns `None` if no condition matches.
+
+Clap resolves conditional defaults from the command line and the defaults of the other args
+alone, so a controlling arg that is set in the config would otherwise be ignored. `config_raw`
+returns the raw config values for an arg id, see [`raw_config_values`].
+*/
+pub fn conditional_default(
+    cmd: &Command,
+    matches: &ArgMatches,
+    conditions: Vec<(clap::Id, ArgPredicate, Resettable<OsStr>)>,
+    config_raw: &dyn Fn(&str) -> Option<Vec<String>>,
+) -> Option<Option<String>> {
+    for (id, predicate, default) in conditions {
+        let explicit = matches
+            .value_source(id.as_str())
+            .is_some_and(|source| source != ValueSource::DefaultValue);
+        let clap_values = || {
+            matches.get_raw(id.as_str()).map(|values| {
+                values
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let values = if explicit {
+            clap_values()
+        } else {
+            config_raw(id.as_str()).or_else(clap_values)
+        };
+        let Some(values) = values else {
+            continue;
+        };
+
+        let is_match = match predicate {
+            ArgPredicate::IsPresent => true,
+            ArgPredicate::Equals(expected) => {
+                let expected = expected.to_string_lossy();
+                let ignore_case = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == &id)
+                    .is_some_and(|arg| arg.is_ignore_case_set());
+                values.iter().any(|v| {
+                    if ignore_case {
+                        v.eq_ignore_ascii_case(&expected)
+                    } else {
+                        *v == expected
+                    }
+                })
+            }
+        };
+        if is_match {
+            return Some(match default {
+                Resettable::Value(default) => Some(default.to_string_lossy().into_owned()),
+                Resettable::Reset => None,
+            });
+        }
+    }
+    None
+}
+
+/**
+Merge `key=value` pairs from the command line into a config map, keeping the config entries for
+the other keys. An empty value (`key=`) removes the key.
+
+The config entries keep their order, and new keys are added at the end.
+*/
+pub fn deep_merge_map(
+    base: impl IntoIterator<Item = (String, String)>,
+    overrides: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut merged: Vec<(String, String)> = base.into_iter().collect();
+    for (key, value) in overrides {
+        let existing = merged.iter().position(|(k, _)| *k == key);
+        match (existing, value.is_empty()) {
+            (Some(i), true) => {
+                merged.remove(i);
+            }
+            (Some(i), false) => merged[i].1 = value,
+            (None, true) => (),
+            (None, false) => merged.push((key, value)),
+        }
+    }
+    merged
+}
+
+/// Merge a config value with the same value from a higher config layer, which replaces it.
+pub fn merge_replace<T>(lower: Option<T>, higher: Option<T>) -> Option<T> {
+    higher.or(lower)
+}
+
+/// Merge a config list with the same list from a higher config layer, adding the higher values
+/// after the lower ones.
+pub fn merge_append<T>(lower: Option<Vec<T>>, higher: Option<Vec<T>>) -> Option<Vec<T>> {
+    match (lower, higher) {
+        (Some(mut lower), Some(higher)) => {
+            lower.extend(higher);
+            Some(lower)
+        }
+        (lower, higher) => higher.or(lower),
+    }
+}
+
+/// Merge a config list with the same list from a higher config layer, adding the higher values
+/// before the lower ones.
+pub fn merge_prepend<T>(lower: Option<Vec<T>>, higher: Option<Vec<T>>) -> Option<Vec<T>> {
+    merge_append(higher, lower)
+}
+
+/// Remove the keys with an empty value from a merged config map with `merge = "deep"`, as an
+/// empty value removes the key, whether it's in a higher layer or in the only one.
+pub fn remove_empty_values(
+    map: Option<impl IntoIterator<Item = (String, String)>>,
+) -> Option<Vec<(String, String)>> {
+    map.map(|map| {
+        map.into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .collect()
+    })
+}
+
+/// Merge a config map with the same map from a higher config layer key by key, where an empty
+/// value in the higher layer removes the key. Empty values that don't override a key are removed
+/// by [`remove_empty_values`] after merging.
+pub fn merge_deep(
+    lower: Option<BTreeMap<String, String>>,
+    higher: Option<BTreeMap<String, String>>,
+) -> Option<BTreeMap<String, String>> {
+    match (lower, higher) {
+        (Some(mut lower), Some(higher)) => {
+            for (key, value) in higher {
+                if value.is_empty() {
+                    lower.remove(&key);
+                } else {
+                    lower.insert(key, value);
+                }
+            }
+            Some(lower)
+        }
+        (lower, higher) => higher.or(lower),
+    }
+}
+
+/// Merge a nested config (e.g. the config for a subcommand) with the same config from a higher
+/// config layer, using its generated `merge` method.
+pub fn merge_nested<T>(
+    lower: Option<T>,
+    higher: Option<T>,
+    merge: impl FnOnce(T, T) -> T,
+) -> Option<T> {
+    match (lower, higher) {
+        (Some(lower), Some(higher)) => Some(merge(lower, higher)),
+        (lower, higher) => higher.or(lower),
+    }
+}
+
+/**
+Merge a lockable config value with the same value from a higher config layer.
+
+A locked value can't be overridden by higher layers, so e.g. a system config can lock values for
+all users. Otherwise the values are merged with `merge`, and the higher layer decides whether the
+result is locked.
+*/
+pub fn merge_lockable<T>(
+    lower: Option<Lockable<T>>,
+    higher: Option<Lockable<T>>,
+    merge: impl FnOnce(Option<T>, Option<T>) -> Option<T>,
+) -> Option<Lockable<T>> {
+    match (lower, higher) {
+        (Some(lower), _) if lower.is_locked() => Some(lower),
+        (Some(lower), Some(higher)) => {
+            let locked = higher.is_locked();
+            let value = merge(Some(lower.into_value()), Some(higher.into_value()))
+                .expect("merging two values returns a value");
+            Some(if locked {
+                Lockable::Locked { value, locked }
+            } else {
+                Lockable::Value(value)
+            })
+        }
+        (lower, higher) => higher.or(lower),
+    }
+}
+
+/**
+Merge a nullable config value with the same value from a higher config layer.
+
+An explicit `null` in the higher layer resets the value, otherwise the values are merged with
+`merge`.
+*/
+pub fn merge_nullable<T>(
+    lower: Option<Option<T>>,
+    higher: Option<Option<T>>,
+    merge: impl FnOnce(Option<T>, Option<T>) -> Option<T>,
+) -> Option<Option<T>> {
+    match higher {
+        None => lower,
+        Some(None) => Some(None),
+        Some(higher) => Some(merge(lower.flatten(), higher)),
+    }
+}
+
+/// Join a config path prefix and a key, e.g. `subcommand_a` and `flag_a` to `subcommand_a.flag_a`.
+pub fn config_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// A value set in a config layer, see `Config::__config_values`.
+pub struct LayerValue {
+    /// The config path of the value.
+    pub key: String,
+    /// Whether the value is locked.
+    pub locked: bool,
+    /// The raw value, see [`raw_config_values`].
+    pub raw: Vec<String>,
+}
+
+/**
+Records the [`Source`] of each value while merging.
+
+The generated code pushes the config path of each subcommand before merging it, so that the
+values are recorded with their full path in the config.
+*/
+#[derive(Default)]
+pub struct SourceRecorder {
+    /// The config path of the subcommand being merged.
+    prefix: Vec<String>,
+    /// The config layers that set each config path, highest precedence first, and whether the
+    /// value was locked.
+    layers: BTreeMap<String, Vec<(String, Vec<String>, bool)>>,
+    sources: Sources,
+}
+
+impl SourceRecorder {
+    /// Create a recorder for config layers with the given names and values, lowest precedence
+    /// first.
+    pub fn new(layers: Vec<(String, Vec<LayerValue>)>) -> Self {
+        let mut layers_by_key: BTreeMap<String, Vec<(String, Vec<String>, bool)>> =
+            BTreeMap::new();
+        for (layer, values) in layers {
+            for value in values {
+                layers_by_key
+                    .entry(value.key)
+                    .or_default()
+                    .insert(0, (layer.clone(), value.raw, value.locked));
+            }
+        }
+        Self {
+            layers: layers_by_key,
+            ..Self::default()
+        }
+    }
+
+    /// Start merging the subcommand with config key `key`.
+    pub fn push(&mut self, key: &str) {
+        self.prefix.push(key.to_owned());
+    }
+
+    /// Finish merging the current subcommand.
+    pub fn pop(&mut self) {
+        self.prefix.pop();
+    }
+
+    /**
+    Check whether a locked config value wins over the clap value for the arg with id `id` and
+    config key `key`, which came from `value_source`.
+
+    Fails if the command line or an env var tried to override the locked value.
+    */
+    pub fn config_locked(
+        &mut self,
+        cmd: &Command,
+        id: &str,
+        key: &str,
+        locked: bool,
+        has_config_value: bool,
+        value_source: ValueSource,
+    ) -> Result<bool, clap::Error> {
+        if !locked || !has_config_value {
+            return Ok(false);
+        }
+        let source = match value_source {
+            ValueSource::CommandLine => Some(format!("'{}'", display_arg(cmd, id))),
+            ValueSource::EnvVariable => cmd
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_env())
+                .map(|env| format!("env var {}", env.to_string_lossy())),
+            _ => None,
+        };
+        if let Some(source) = source {
+            let path = config_path(&self.prefix.join("."), key);
+            return Err(clap::Error::raw(
+                ErrorKind::ArgumentConflict,
+                format!("{source} can't be used, as config key {path} is locked\n"),
+            ));
+        }
+        Ok(true)
+    }
+
+    /**
+    Record the source of the arg with id `id` and config key `key`, along with the values from the
+    other sources that it shadowed.
+
+    `from_config` is whether the config value won, and `conditional_default` is the raw default
+    from `default_value_if`, which clap doesn't know about.
+
+    Has to be called before the value is removed from `matches`.
+    */
+    pub fn record(
+        &mut self,
+        cmd: &Command,
+        matches: &ArgMatches,
+        id: &str,
+        key: &str,
+        from_config: bool,
+        conditional_default: Option<&str>,
+    ) {
+        let path = config_path(&self.prefix.join("."), key);
+        let arg = cmd.get_arguments().find(|arg| arg.get_id() == id);
+        let clap_value = || -> Vec<String> {
+            matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect()
+        };
+
+        // All the values, highest precedence first.
+        let mut candidates = vec![];
+        let clap_source = matches.value_source(id);
+        if clap_source == Some(ValueSource::CommandLine) {
+            candidates.push((Source::CommandLine, clap_value()));
+        }
+        if let Some(env) = arg.and_then(Arg::get_env) {
+            let name = env.to_string_lossy().into_owned();
+            if clap_source == Some(ValueSource::EnvVariable) {
+                candidates.push((Source::EnvVariable(name), clap_value()));
+            } else if let Some(value) = std::env::var_os(env) {
+                candidates.push((
+                    Source::EnvVariable(name),
+                    vec![value.to_string_lossy().into_owned()],
+                ));
+            }
+        }
+        // A locked layer wins over the layers above it.
+        let mut config_values = self.layers.get(&path).cloned().unwrap_or_default();
+        if let Some(locked) = config_values.iter().rposition(|(_, _, locked)| *locked) {
+            let locked = config_values.remove(locked);
+            config_values.insert(0, locked);
+        }
+        let config_start = candidates.len();
+        candidates.extend(
+            config_values
+                .into_iter()
+                .map(|(layer, raw, _)| (Source::Config(layer), raw)),
+        );
+        if let Some(default) = conditional_default {
+            candidates.push((Source::Default, vec![default.to_owned()]));
+        } else if let Some(arg) = arg.filter(|arg| !arg.get_default_values().is_empty()) {
+            let default = arg
+                .get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            candidates.push((Source::Default, default));
+        }
+
+        let winner = if from_config {
+            // The config value can be missing if it was read from a config without layers.
+            (config_start < candidates.len()
+                && matches!(candidates[config_start].0, Source::Config(_)))
+            .then_some(config_start)
+        } else {
+            candidates
+                .iter()
+                .position(|(source, _)| !matches!(source, Source::Config(_)))
+        };
+        let (source, value) = match winner {
+            Some(winner) => candidates.remove(winner),
+            None if from_config => (Source::Config(String::new()), vec![]),
+            None => (Source::Unset, vec![]),
+        };
+        self.sources.insert(
+            path,
+            Origin {
+                source,
+                value,
+                shadowed: candidates,
+            },
+        );
+    }
+
+    /// Record that the config section of the subcommand with config key `key` wasn't used, as
+    /// the subcommand wasn't invoked.
+    pub fn unused_section(&mut self, key: &str) {
+        let path = config_path(&self.prefix.join("."), key);
+        self.sources.insert_unused_section(path);
+    }
+
+    /// The recorded sources.
+    pub fn finish(self) -> Sources {
+        self.sources
+    }
+}
+
+/// Render an example config for `section`, using the values of `defaults` as the example values.
+pub fn example_config<T: Serialize>(
+    section: &ExampleSection,
+    defaults: &T,
+    format: ExampleFormat,
+) -> String {
+    let defaults = serde_json::to_value(defaults).unwrap_or_default();
+    section.render(&defaults, format)
+}
+
+/**
+Deserialize a config, failing with a message that suggests the most similar valid key for each
+key that isn't in `keys`.
+*/
+pub fn deserialize_strict<'de, D, T>(deserializer: D, keys: &ConfigKeys) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    use serde::de::Error;
+
+    let value = serde_json::Value::deserialize(deserializer)?;
+    keys.check(&value).map_err(D::Error::custom)?;
+    T::deserialize(value).map_err(D::Error::custom)
+}
+
+/**
+Fail for the first of the `unknown` config sections of the subcommand with config path `prefix`,
+i.e. sections that don't match any of the `known` subcommand sections, e.g. after a subcommand was
+renamed.
+*/
+pub fn check_unknown_sections<'a>(
+    prefix: &str,
+    mut unknown: impl Iterator<Item = &'a str>,
+    known: &[&'static str],
+) -> Result<(), clap::Error> {
+    let Some(key) = unknown.next() else {
+        return Ok(());
+    };
+    let mut message = format!(
+        "config section '{}' doesn't match any subcommand\n",
+        config_path(prefix, key)
+    );
+    if let Some(similar) = crate::strict::similar(key, known.iter().copied()) {
+        message.push_str(&format!(
+            "\n  tip: a similar subcommand exists: '{}'\n",
+            config_path(prefix, similar)
+        ));
+    }
+    Err(clap::Error::raw(ErrorKind::InvalidSubcommand, message))
+}
+
+/**
+Deserialize the value of the global arg with config key `key`, set in a subcommand section, into
+a config with only that value set.
+*/
+pub fn global_config<T: serde::de::DeserializeOwned>(
+    key: &str,
+    value: serde_json::Value,
+) -> Result<T, clap::Error> {
+    let config = serde_json::Value::Object([(key.to_owned(), value)].into_iter().collect());
+    serde_json::from_value(config).map_err(|e| {
+        clap::Error::raw(
+            ErrorKind::InvalidValue,
+            format!("invalid value for config key {key} in a subcommand section: {e}\n"),
+        )
+    })
+}
+
+/// Deserialize the shared defaults section with config key `key` into the config of a subcommand,
+/// ignoring the keys of the other subcommands.
+pub fn section_defaults<T: serde::de::DeserializeOwned>(
+    key: &str,
+    defaults: OtherKeys,
+) -> Result<T, clap::Error> {
+    let defaults = serde_json::to_value(defaults).expect("Failed to serialize the defaults.");
+    serde_json::from_value(defaults).map_err(|e| {
+        clap::Error::raw(
+            ErrorKind::InvalidValue,
+            format!("invalid value in config section {key}: {e}\n"),
+        )
+    })
+}
@@ -0,0 +1,125 @@
+/*!
+Command aliases defined in the config, like `git config alias.co checkout`.
+*/
+
+use clap::Command;
+use clap::error::ErrorKind;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+/**
+The aliases section of a config, mapping each alias to the args it expands to, e.g.
+`deploy-prod: deploy --env prod --confirm`.
+
+Read into a hidden field of the generated config structs by `#[clap_config(aliases_key)]`.
+*/
+#[doc(hidden)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Aliases(BTreeMap<String, String>);
+
+impl Aliases {
+    /// Merge with the aliases of a higher precedence config layer, which win for the same alias.
+    pub fn merge(mut self, higher: Self) -> Self {
+        self.0.extend(higher.0);
+        self
+    }
+
+    /**
+    Expand the alias at the start of `args`, the full command line including the binary name,
+    until it no longer starts with an alias.
+
+    Aliases can't shadow the subcommands of `cmd`, and `key` is the config key of the aliases
+    section, for errors.
+    */
+    pub fn expand(
+        &self,
+        cmd: &Command,
+        key: &str,
+        mut args: Vec<OsString>,
+    ) -> Result<Vec<OsString>, clap::Error> {
+        // The aliases expanded so far, to report recursive aliases instead of looping forever.
+        let mut expanded: Vec<String> = vec![];
+        while let Some(name) = args.get(1).and_then(|arg| arg.to_str()) {
+            if cmd.find_subcommand(name).is_some() {
+                break;
+            }
+            let Some(alias) = self.0.get(name) else {
+                break;
+            };
+            let name = name.to_owned();
+            if expanded.contains(&name) {
+                expanded.push(name.clone());
+                return Err(alias_error(
+                    key,
+                    &name,
+                    &format!("it expands to itself: {}", expanded.join(" -> ")),
+                ));
+            }
+            let alias_args = split_args(alias).map_err(|e| alias_error(key, &name, e))?;
+            if alias_args.is_empty() {
+                return Err(alias_error(key, &name, "it is empty"));
+            }
+            args.splice(1..2, alias_args.into_iter().map(OsString::from));
+            expanded.push(name);
+        }
+        Ok(args)
+    }
+}
+
+fn alias_error(key: &str, name: &str, reason: &str) -> clap::Error {
+    clap::Error::raw(
+        ErrorKind::InvalidValue,
+        format!("invalid alias '{name}' in config section {key}: {reason}\n"),
+    )
+}
+
+/**
+Split an alias into args like a shell does: on whitespace, except in single or double quotes, with
+`\` escaping the next character outside single quotes.
+*/
+fn split_args(alias: &str) -> Result<Vec<String>, &'static str> {
+    let mut args = vec![];
+    // The current arg, or `None` between args. An empty quoted arg is still an arg.
+    let mut arg: Option<String> = None;
+    let mut chars = alias.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("it has an unterminated quote"),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => arg.extend(['\\', c]),
+                            None => return Err("it has an unterminated quote"),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("it has an unterminated quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err("it ends with an escape"),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
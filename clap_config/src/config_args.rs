@@ -0,0 +1,180 @@
+/*!
+Extra command line args from the config, like ripgrep's `RIPGREP_CONFIG_PATH`.
+*/
+
+use clap::Arg;
+use clap::ArgAction;
+use clap::Command;
+use clap::Id;
+use std::ffi::OsString;
+
+/**
+The `args` lists of a config and of its subcommand sections.
+
+Built by the generated `__config_args` methods.
+*/
+#[doc(hidden)]
+#[derive(Default)]
+pub struct ConfigArgs {
+    args: Vec<String>,
+    /// The args of each subcommand, by subcommand name.
+    sections: Vec<(&'static str, ConfigArgs)>,
+}
+
+impl ConfigArgs {
+    /// Add args for this command.
+    pub fn args(&mut self, args: &[String]) {
+        self.args.extend_from_slice(args);
+    }
+
+    /// Add the args of the subcommand with name `name`.
+    pub fn section(&mut self, name: &'static str, args: ConfigArgs) {
+        self.sections.push((name, args));
+    }
+
+    /// Add the subcommand sections of a subcommand enum.
+    pub fn flatten(&mut self, args: ConfigArgs) {
+        self.sections.extend(args.sections);
+    }
+
+    /// Whether there are no args for this command or any of its subcommands.
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty() && self.sections.iter().all(|(_, section)| section.is_empty())
+    }
+
+    /**
+    Insert the args into `args`, the full command line including the binary name: the args of
+    each command go right after its name, so the args from the command line come after them and
+    win.
+    */
+    pub fn insert(&self, cmd: &Command, args: &[OsString]) -> Vec<OsString> {
+        let Some((name, rest)) = args.split_first() else {
+            return vec![];
+        };
+        let mut inserted = vec![name.clone()];
+        inserted.extend(self.args.iter().map(OsString::from));
+        match find_subcommand(cmd, rest) {
+            Some((index, subcommand)) => {
+                inserted.extend_from_slice(&rest[..index]);
+                let section = self
+                    .sections
+                    .iter()
+                    .find(|(name, _)| *name == subcommand.get_name());
+                match section {
+                    Some((_, section)) => {
+                        inserted.extend(section.insert(subcommand, &rest[index..]));
+                    }
+                    None => inserted.extend_from_slice(&rest[index..]),
+                }
+            }
+            None => inserted.extend_from_slice(rest),
+        }
+        inserted
+    }
+
+    /**
+    Let the args set by the config be repeated in `cmd`, the command to parse with, so the ones
+    from the command line replace them instead of being rejected. Other args can still only be
+    given once.
+    */
+    pub fn override_command(&self, mut cmd: Command) -> Command {
+        for id in arg_ids(&cmd, &self.args) {
+            cmd = cmd.mut_arg(id.clone(), |arg| arg.overrides_with(id));
+        }
+        for (name, section) in &self.sections {
+            if cmd.find_subcommand(name).is_some() {
+                cmd = cmd.mut_subcommand(*name, |subcommand| section.override_command(subcommand));
+            }
+        }
+        cmd
+    }
+}
+
+/**
+The ids of the options and flags of `cmd` that are set in `args` and can only be given once, i.e.
+not lists or counts.
+*/
+fn arg_ids(cmd: &Command, args: &[String]) -> Vec<Id> {
+    let mut ids = vec![];
+    let mut found = |arg: Option<&Arg>| {
+        let arg = arg?;
+        let once = matches!(
+            arg.get_action(),
+            ArgAction::Set | ArgAction::SetTrue | ArgAction::SetFalse
+        );
+        if once && !ids.contains(arg.get_id()) {
+            ids.push(arg.get_id().clone());
+        }
+        Some(arg.get_action().takes_values())
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (long, value) = match long.split_once('=') {
+                Some((long, _)) => (long, true),
+                None => (long, false),
+            };
+            let arg = cmd.get_arguments().find(|arg| arg.get_long() == Some(long));
+            if found(arg) == Some(true) && !value {
+                args.next();
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            for (position, short) in shorts.char_indices() {
+                let arg = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_short() == Some(short));
+                if found(arg) == Some(true) {
+                    if position + short.len_utf8() == shorts.len() {
+                        args.next();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    ids
+}
+
+/**
+The index in `args` and the command of the subcommand of `cmd` that's invoked, if any.
+
+Skips over flags and the values of options, so that e.g. the value of `--name deploy` isn't taken
+for the `deploy` subcommand.
+*/
+fn find_subcommand<'a>(cmd: &'a Command, args: &[OsString]) -> Option<(usize, &'a Command)> {
+    let takes_value = |arg: Option<&Arg>| arg.is_some_and(|arg| arg.get_action().takes_values());
+    let mut args = args.iter().enumerate();
+    while let Some((index, arg)) = args.next() {
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        if arg == "--" {
+            return None;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            // The value of `--name=value` is part of the same arg.
+            let arg = cmd.get_arguments().find(|arg| arg.get_long() == Some(long));
+            if takes_value(arg) {
+                args.next();
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // Short flags can be combined, and the value of the first option in `-abvalue` is
+            // the rest of the arg, or else the next arg.
+            for (position, short) in shorts.char_indices() {
+                let arg = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_short() == Some(short));
+                if takes_value(arg) {
+                    if position + short.len_utf8() == shorts.len() {
+                        args.next();
+                    }
+                    break;
+                }
+            }
+        } else if let Some(subcommand) = cmd.find_subcommand(arg) {
+            return Some((index, subcommand));
+        }
+    }
+    None
+}
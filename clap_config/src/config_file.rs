@@ -0,0 +1,156 @@
+/*!
+Reading a config file and parsing the command line with it in one call.
+*/
+
+use clap::ArgMatches;
+use clap::Command;
+use clap::error::ErrorKind;
+use serde::de::DeserializeOwned;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+/**
+Merging the command line with a config, implemented by the `ClapConfig` derive for structs.
+
+This is what [`ParseWithConfig`] is built on.
+*/
+#[doc(hidden)]
+pub trait MergeConfig: Sized {
+    /// The generated config struct.
+    type Config: DeserializeOwned;
+
+    /// Merge the values from the command line and the config.
+    fn try_from_merged(
+        matches: ArgMatches,
+        config: Option<Self::Config>,
+    ) -> Result<Self, clap::Error>;
+
+    /**
+    Expand an alias and insert the extra args from the config in `args`, the full command line
+    including the binary name. Also returns `cmd`, the command to parse them with, updated so the
+    command line can override the args from the config.
+    */
+    fn prepare_args(
+        cmd: Command,
+        args: Vec<OsString>,
+        config: Option<&Self::Config>,
+    ) -> Result<(Command, Vec<OsString>), clap::Error>;
+
+    /**
+    Relax the required args, `requires`, and required group rules of `cmd`, so they can be
+    satisfied by the config. They are checked again after merging.
+    */
+    fn relax_command(cmd: clap::Command) -> clap::Command;
+}
+
+/**
+Parse the command line and merge it with a config file, like [`clap::Parser::parse`].
+
+The format of the config file is chosen by its extension: YAML (`.yaml` or `.yml`), TOML
+(`.toml`), or JSON (`.json`), with the `yaml`, `toml`, and `json` features, which are enabled by
+default.
+
+Implemented for every `clap::Parser` struct that derives `ClapConfig`.
+*/
+pub trait ParseWithConfig: clap::Parser + MergeConfig {
+    /**
+    Parse the command line, merged with the config file at `path`, exiting with a clap error on
+    failure.
+
+    The format of the config file is chosen by its extension, see [`ParseWithConfig`]. A missing
+    config file is treated as an empty config.
+    */
+    fn parse_with_config(path: impl AsRef<Path>) -> Self {
+        Self::try_parse_with_config(path).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse the command line, merged with the config file at `path`, see `parse_with_config`.
+    fn try_parse_with_config(path: impl AsRef<Path>) -> Result<Self, clap::Error> {
+        Self::try_parse_from_with_config(std::env::args_os(), path)
+    }
+
+    /// Parse `args`, merged with the config file at `path`, exiting with a clap error on failure.
+    fn parse_from_with_config<I, T>(args: I, path: impl AsRef<Path>) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        Self::try_parse_from_with_config(args, path).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args`, merged with the config file at `path`, see `parse_from_with_config`.
+    fn try_parse_from_with_config<I, T>(
+        args: I,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let config = read_config_file(path.as_ref())?;
+        let args = args.into_iter().map(Into::into).collect();
+        let command = <Self as MergeConfig>::relax_command(Self::command());
+        let (command, args) = Self::prepare_args(command, args, config.as_ref())?;
+        let matches = command.try_get_matches_from(args)?;
+        Self::try_from_merged(matches, config)
+    }
+}
+
+impl<T: clap::Parser + MergeConfig> ParseWithConfig for T {}
+
+/// Read the config file at `path`, in the format of its extension, or `None` if it doesn't exist.
+// Without any format, every config file is unsupported.
+#[cfg_attr(
+    not(any(feature = "yaml", feature = "toml", feature = "json")),
+    allow(unused_variables)
+)]
+fn read_config_file<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, clap::Error> {
+    let display = path.display();
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(clap::Error::raw(
+                ErrorKind::Io,
+                format!("failed to read config file {display}: {e}\n"),
+            ));
+        }
+    };
+    let invalid = |e: String| {
+        clap::Error::raw(
+            ErrorKind::InvalidValue,
+            format!("invalid config file {display}: {e}\n"),
+        )
+    };
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let config: Result<T, clap::Error> = match extension {
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|e| invalid(e.to_string())),
+        #[cfg(feature = "toml")]
+        Some("toml") => toml::from_str(&contents).map_err(|e| invalid(e.to_string())),
+        #[cfg(feature = "json")]
+        Some("json") => serde_json::from_str(&contents).map_err(|e| invalid(e.to_string())),
+        _ => {
+            let supported: &[&str] = &[
+                #[cfg(feature = "yaml")]
+                "yaml",
+                #[cfg(feature = "yaml")]
+                "yml",
+                #[cfg(feature = "toml")]
+                "toml",
+                #[cfg(feature = "json")]
+                "json",
+            ];
+            Err(clap::Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "unsupported format for config file {display}: expected one of the \
+                     extensions [{}]\n",
+                    supported.join(", ")
+                ),
+            ))
+        }
+    };
+    config.map(Some)
+}
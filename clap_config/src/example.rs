@@ -0,0 +1,267 @@
+/*!
+Example config files generated from the clap definition.
+*/
+
+use clap::Arg;
+use clap::Command;
+use serde_json::Value;
+use std::fmt::Write;
+
+/// The format of a generated example config, see the generated `Config::example_config` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExampleFormat {
+    /// YAML, with subcommands as nested mappings.
+    Yaml,
+    /// TOML, with subcommands as tables.
+    Toml,
+}
+
+/// A documented config key in an example config.
+struct ExampleKey {
+    key: String,
+    help: Option<String>,
+    defaults: Vec<String>,
+    possible_values: Vec<String>,
+    flag: Option<String>,
+    env: Option<String>,
+    value_name: String,
+}
+
+/**
+The config keys of a struct, and the sections of its subcommands, in field order.
+
+Built by the generated `__example_section` methods.
+*/
+#[doc(hidden)]
+#[derive(Default)]
+pub struct ExampleSection {
+    keys: Vec<ExampleKey>,
+    sections: Vec<(String, ExampleSection)>,
+}
+
+impl ExampleSection {
+    /// Add the config key `key` for the arg with id `id`, unless the arg is hidden.
+    pub fn key(&mut self, cmd: &Command, id: &str, key: &str) {
+        self.key_with_spelling(cmd, id, key, ToOwned::to_owned);
+    }
+
+    /**
+    Add the config key `key` for the arg with id `id`, unless the arg is hidden, where `spelling`
+    converts the default and possible values from their command line spelling to the config's.
+    */
+    pub fn key_with_spelling(
+        &mut self,
+        cmd: &Command,
+        id: &str,
+        key: &str,
+        spelling: impl Fn(&str) -> String,
+    ) {
+        let arg = cmd
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .unwrap_or_else(|| panic!("Expected the command to have an arg with id '{id}'."));
+        if arg.is_hide_set() {
+            return;
+        }
+        self.keys.push(ExampleKey {
+            key: key.to_owned(),
+            help: arg
+                .get_long_help()
+                .or_else(|| arg.get_help())
+                .map(|help| help.to_string()),
+            defaults: arg
+                .get_default_values()
+                .iter()
+                .map(|value| spelling(&value.to_string_lossy()))
+                .collect(),
+            possible_values: if arg.get_action().takes_values() {
+                arg.get_possible_values()
+                    .iter()
+                    .filter(|value| !value.is_hide_set())
+                    .map(|value| spelling(value.get_name()))
+                    .collect()
+            } else {
+                vec![]
+            },
+            flag: flag(arg),
+            env: arg.get_env().map(|env| env.to_string_lossy().into_owned()),
+            value_name: arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map_or_else(|| id.to_uppercase(), ToString::to_string),
+        });
+    }
+
+    /// Add the section of the subcommand with config key `key`.
+    pub fn section(&mut self, key: &str, section: ExampleSection) {
+        self.sections.push((key.to_owned(), section));
+    }
+
+    /// Add the subcommand sections of a subcommand enum.
+    pub fn flatten(&mut self, section: ExampleSection) {
+        self.keys.extend(section.keys);
+        self.sections.extend(section.sections);
+    }
+
+    /**
+    Render the example config, with every key commented out.
+
+    `defaults` is the config with the clap defaults, which are used as the example values.
+    */
+    pub fn render(&self, defaults: &Value, format: ExampleFormat) -> String {
+        let mut out = String::new();
+        match format {
+            ExampleFormat::Yaml => self.render_yaml(defaults, 0, &mut out),
+            ExampleFormat::Toml => self.render_toml(defaults, &[], &mut out),
+        }
+        out
+    }
+
+    fn render_yaml(&self, defaults: &Value, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        for key in &self.keys {
+            key.render_comment(&indent, out);
+            let value = defaults
+                .get(&key.key)
+                .map_or_else(|| format!("<{}>", key.value_name), yaml_value);
+            writeln!(out, "{indent}# {}: {value}", key.key).unwrap();
+            out.push('\n');
+        }
+        for (name, section) in &self.sections {
+            writeln!(out, "{indent}{name}:").unwrap();
+            section.render_yaml(defaults.get(name).unwrap_or(&Value::Null), depth + 1, out);
+        }
+    }
+
+    fn render_toml(&self, defaults: &Value, path: &[&str], out: &mut String) {
+        for key in &self.keys {
+            key.render_comment("", out);
+            let value = defaults
+                .get(&key.key)
+                .map_or_else(|| format!("<{}>", key.value_name), toml_value);
+            writeln!(out, "# {} = {value}", toml_key(&key.key)).unwrap();
+            out.push('\n');
+        }
+        for (name, section) in &self.sections {
+            let path = [path, &[name.as_str()]].concat();
+            let header: Vec<_> = path.iter().map(|key| toml_key(key)).collect();
+            writeln!(out, "[{}]", header.join(".")).unwrap();
+            section.render_toml(defaults.get(name).unwrap_or(&Value::Null), &path, out);
+        }
+    }
+}
+
+impl ExampleKey {
+    /// Render the help text, default, possible values, flag, and env var as comment lines.
+    fn render_comment(&self, indent: &str, out: &mut String) {
+        let mut lines = vec![];
+        if let Some(help) = &self.help {
+            lines.extend(help.trim_end().lines().map(str::to_owned));
+            lines.push(String::new());
+        }
+        if !self.defaults.is_empty() {
+            lines.push(format!("Default: {}", self.defaults.join(", ")));
+        }
+        if !self.possible_values.is_empty() {
+            lines.push(format!(
+                "Possible values: {}",
+                self.possible_values.join(", ")
+            ));
+        }
+        if let Some(flag) = &self.flag {
+            lines.push(format!("Command line: {flag}"));
+        }
+        if let Some(env) = &self.env {
+            lines.push(format!("Env var: {env}"));
+        }
+        if lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        for line in lines {
+            if line.is_empty() {
+                writeln!(out, "{indent}#").unwrap();
+            } else {
+                writeln!(out, "{indent}# {line}").unwrap();
+            }
+        }
+    }
+}
+
+/// The command line flag of an arg, e.g. `--port` or `-p, --port`.
+fn flag(arg: &Arg) -> Option<String> {
+    match (arg.get_short(), arg.get_long()) {
+        (Some(short), Some(long)) => Some(format!("-{short}, --{long}")),
+        (Some(short), None) => Some(format!("-{short}")),
+        (None, Some(long)) => Some(format!("--{long}")),
+        (None, None) => None,
+    }
+}
+
+/// Render a value in YAML flow style.
+fn yaml_value(value: &Value) -> String {
+    match value {
+        Value::String(s) if is_plain_yaml(s) => s.clone(),
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(yaml_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}: {}",
+                        yaml_value(&Value::String(k.clone())),
+                        yaml_value(v)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        // JSON scalars are valid YAML.
+        other => other.to_string(),
+    }
+}
+
+/// Whether a string can be written without quotes in YAML, and still be read as a string.
+fn is_plain_yaml(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c))
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n"
+        )
+}
+
+/// Render a value as a TOML value.
+fn toml_value(value: &Value) -> String {
+    match value {
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(toml_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(k, v)| format!("{} = {}", toml_key(k), toml_value(v)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        // JSON strings, numbers, and booleans are valid TOML.
+        other => other.to_string(),
+    }
+}
+
+/// Render a TOML key, quoting it if it isn't a bare key.
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_owned()
+    } else {
+        Value::String(key.to_owned()).to_string()
+    }
+}
@@ -0,0 +1,72 @@
+/*!
+Config values that can be locked, so the command line and env vars can't override them.
+*/
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/**
+The config value of a field marked `#[clap_config(lockable)]`.
+
+In the config this is either the plain value, or the value with a `locked` flag:
+
+```yaml
+telemetry:
+  value: false
+  locked: true
+```
+
+A locked value can't be overridden: setting the arg on the command line or with an env var is an
+error when merging.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Lockable<T> {
+    /// A value with an explicit `locked` flag.
+    Locked {
+        /// The config value.
+        value: T,
+        /// Whether the command line and env vars are prevented from overriding the value.
+        locked: bool,
+    },
+    /// A plain value, which is never locked.
+    Value(T),
+}
+
+impl<T> Lockable<T> {
+    /// Whether the value is locked.
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Self::Locked { locked: true, .. })
+    }
+
+    /// A reference to the config value, whether or not it is locked.
+    pub fn value(&self) -> &T {
+        match self {
+            Self::Locked { value, .. } | Self::Value(value) => value,
+        }
+    }
+
+    /// The config value, whether or not it is locked.
+    pub fn into_value(self) -> T {
+        match self {
+            Self::Locked { value, .. } | Self::Value(value) => value,
+        }
+    }
+
+    /// Convert the value, keeping the `locked` flag.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Lockable<U> {
+        match self {
+            Self::Locked { value, locked } => Lockable::Locked {
+                value: f(value),
+                locked,
+            },
+            Self::Value(value) => Lockable::Value(f(value)),
+        }
+    }
+}
+
+impl<T> From<T> for Lockable<T> {
+    fn from(value: T) -> Self {
+        Self::Value(value)
+    }
+}
@@ -0,0 +1,208 @@
+/*!
+Where each merged value came from.
+*/
+
+use std::fmt;
+
+/// Where a merged value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Passed on the command line.
+    CommandLine,
+    /// Set by the env var with this name.
+    EnvVariable(String),
+    /// Set in the config, by the config layer with this name.
+    Config(String),
+    /// The clap default value, or a `default_value_if` default.
+    Default,
+    /// Not set anywhere, e.g. an `Option` field that is `None`.
+    Unset,
+}
+
+impl Source {
+    fn to_json(&self) -> serde_json::Value {
+        let (kind, name) = match self {
+            Self::CommandLine => ("command_line", None),
+            Self::EnvVariable(name) => ("env", Some(name)),
+            Self::Config(layer) => ("config", Some(layer)),
+            Self::Default => ("default", None),
+            Self::Unset => ("unset", None),
+        };
+        let mut json = serde_json::json!({ "kind": kind });
+        if let Some(name) = name {
+            json["name"] = name.as_str().into();
+        }
+        json
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandLine => write!(f, "command line"),
+            Self::EnvVariable(name) => write!(f, "env var {name}"),
+            Self::Config(layer) => write!(f, "config {layer}"),
+            Self::Default => write!(f, "default"),
+            Self::Unset => write!(f, "unset"),
+        }
+    }
+}
+
+/// Where a merged value came from, and the values from other sources that it shadowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// Where the value came from.
+    pub source: Source,
+    /// The raw value, with one entry per value for args that take multiple values.
+    pub value: Vec<String>,
+    /// The raw values that were set by other sources but not used, highest precedence first.
+    pub shadowed: Vec<(Source, Vec<String>)>,
+}
+
+impl Origin {
+    fn to_json(&self, key: &str) -> serde_json::Value {
+        serde_json::json!({
+            "key": key,
+            "value": self.value,
+            "source": self.source.to_json(),
+            "shadowed": self
+                .shadowed
+                .iter()
+                .map(|(source, value)| serde_json::json!({ "value": value, "source": source.to_json() }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The format of the [`Sources::render`] report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExplainFormat {
+    /// A table with one line per value, followed by the values it shadowed.
+    #[default]
+    Text,
+    /// A JSON array with one object per value.
+    Json,
+}
+
+/**
+The [`Source`] of every merged value, keyed by the path of the value in the config, e.g. `port`, or
+`subcommand_a.flag_a` for a field of a subcommand.
+
+Returned by the generated `from_merged_with_sources` methods.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sources {
+    /// The origins in the order the fields were merged.
+    origins: Vec<(String, Origin)>,
+    /// The config paths of the subcommand sections that weren't used.
+    unused_sections: Vec<String>,
+}
+
+impl Sources {
+    /// The source of the value with this config path.
+    pub fn get(&self, key: &str) -> Option<&Source> {
+        self.origin(key).map(|origin| &origin.source)
+    }
+
+    /// The source, value, and shadowed values of the value with this config path.
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        self.origins
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, origin)| origin)
+    }
+
+    /// The config paths and sources of all the merged values, in field order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Source)> {
+        self.origins
+            .iter()
+            .map(|(key, origin)| (key.as_str(), &origin.source))
+    }
+
+    /// The config paths and origins of all the merged values, in field order.
+    pub fn origins(&self) -> impl Iterator<Item = (&str, &Origin)> {
+        self.origins
+            .iter()
+            .map(|(key, origin)| (key.as_str(), origin))
+    }
+
+    /**
+    The config paths of the subcommand sections in the config that weren't used, because their
+    subcommand wasn't invoked. Sections that don't match any subcommand are an error when merging.
+
+    Every other config value was used, see [`Sources::origins`].
+    */
+    pub fn unused_sections(&self) -> &[String] {
+        &self.unused_sections
+    }
+
+    /**
+    Render a report of each value, where it came from, and the values it shadowed, similar to
+    `git config --list --show-origin`.
+
+    ```text
+    KEY                  VALUE                      SOURCE
+    name                 user                       config ~/.myapp.yaml
+      (shadowed)         system                     config /etc/myapp.yaml
+      (shadowed)         default                    default
+    verbose              true                       command line
+    ```
+    */
+    pub fn render(&self, format: ExplainFormat) -> String {
+        match format {
+            ExplainFormat::Text => self.to_string(),
+            ExplainFormat::Json => {
+                let json: Vec<_> = self
+                    .origins
+                    .iter()
+                    .map(|(key, origin)| origin.to_json(key))
+                    .collect();
+                serde_json::to_string_pretty(&json).expect("Failed to serialize JSON values.")
+            }
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: String, origin: Origin) {
+        self.origins.push((key, origin));
+    }
+
+    pub(crate) fn insert_unused_section(&mut self, key: String) {
+        self.unused_sections.push(key);
+    }
+}
+
+impl fmt::Display for Sources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHADOWED: &str = "  (shadowed)";
+        let rows: Vec<_> = self
+            .origins
+            .iter()
+            .flat_map(|(key, origin)| {
+                std::iter::once((key.as_str(), &origin.value, &origin.source)).chain(
+                    origin
+                        .shadowed
+                        .iter()
+                        .map(|(source, value)| (SHADOWED, value, source)),
+                )
+            })
+            .map(|(key, value, source)| (key, value.join(","), source))
+            .collect();
+        let key_width = rows
+            .iter()
+            .map(|(key, ..)| key.len())
+            .max()
+            .unwrap_or(0)
+            .max(3);
+        let value_width = rows
+            .iter()
+            .map(|(_, value, _)| value.len())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+        writeln!(f, "{:key_width$}  {:value_width$}  SOURCE", "KEY", "VALUE")?;
+        for (key, value, source) in rows {
+            writeln!(f, "{key:key_width$}  {value:value_width$}  {source}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+/*!
+Strict config loading, which reports unknown keys with the most similar valid key.
+*/
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/**
+The valid config keys of a struct, and the keys of its subcommand sections.
+
+Built by the generated `__config_keys` methods.
+*/
+#[doc(hidden)]
+#[derive(Clone, Default)]
+pub struct ConfigKeys {
+    keys: Vec<&'static str>,
+    /// The keys of global args, which can also be set in the subcommand sections.
+    globals: Vec<&'static str>,
+    /// Whether the keys of the global args of parent commands can be set in this section.
+    inherit_globals: bool,
+    /// The key of the section with defaults for every subcommand section.
+    defaults: Option<&'static str>,
+    sections: Vec<(&'static str, ConfigKeys)>,
+}
+
+/// A key in a config file that isn't a valid config key.
+struct UnknownKey {
+    /// The path of the key from the root of the config, e.g. `subcommand_a.flag_strng`.
+    path: String,
+    /// The path of the most similar valid key, if any is similar enough.
+    suggestion: Option<String>,
+}
+
+impl ConfigKeys {
+    /// Add a config key.
+    pub fn key(&mut self, key: &'static str) {
+        self.keys.push(key);
+    }
+
+    /// Add the config key of a global arg.
+    pub fn global(&mut self, key: &'static str) {
+        self.globals.push(key);
+    }
+
+    /// Allow the keys of the global args of parent commands.
+    pub fn inherit_globals(&mut self) {
+        self.inherit_globals = true;
+    }
+
+    /// Add the section with defaults for every subcommand, which can set any key of any
+    /// subcommand section.
+    pub fn defaults(&mut self, key: &'static str) {
+        self.defaults = Some(key);
+    }
+
+    /// Add the section of the subcommand with config key `key`.
+    pub fn section(&mut self, key: &'static str, keys: ConfigKeys) {
+        self.sections.push((key, keys));
+    }
+
+    /// Add the subcommand sections of a subcommand enum.
+    pub fn flatten(&mut self, keys: ConfigKeys) {
+        self.keys.extend(keys.keys);
+        self.defaults = self.defaults.or(keys.defaults);
+        self.sections.extend(keys.sections);
+    }
+
+    /**
+    Check the keys of `value` and of its subcommand sections, returning an error message for the
+    unknown keys, if there are any.
+    */
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        let mut unknown = vec![];
+        self.unknown_keys(value, "", &[], &mut unknown);
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        let messages: Vec<_> = unknown.iter().map(ToString::to_string).collect();
+        Err(messages.join("\n\n"))
+    }
+
+    /// Collect the unknown keys of `value`, where `globals` are the keys of the global args of
+    /// the parent commands.
+    fn unknown_keys(
+        &self,
+        value: &Value,
+        prefix: &str,
+        globals: &[&'static str],
+        unknown: &mut Vec<UnknownKey>,
+    ) {
+        // Values of the wrong type are reported when deserializing the config.
+        let Some(map) = value.as_object() else {
+            return;
+        };
+        let path = |key: &str| {
+            if prefix.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{prefix}.{key}")
+            }
+        };
+        let globals = if self.inherit_globals {
+            [globals, &self.globals].concat()
+        } else {
+            self.globals.clone()
+        };
+        for (key, value) in map {
+            if self.keys.contains(&key.as_str()) || globals.contains(&key.as_str()) {
+                continue;
+            }
+            if let Some((_, section)) = self.sections.iter().find(|(name, _)| name == key) {
+                section.unknown_keys(value, &path(key), &globals, unknown);
+                continue;
+            }
+            if self.defaults == Some(key.as_str()) {
+                self.all_sections()
+                    .unknown_keys(value, &path(key), &globals, unknown);
+                continue;
+            }
+            unknown.push(UnknownKey {
+                path: path(key),
+                suggestion: self.similar_key(key).map(path),
+            });
+        }
+    }
+
+    /// The keys and sections of all the subcommand sections, for the defaults section.
+    fn all_sections(&self) -> ConfigKeys {
+        let mut all = ConfigKeys {
+            inherit_globals: true,
+            ..ConfigKeys::default()
+        };
+        for (_, section) in &self.sections {
+            all.keys.extend(&section.keys);
+            all.globals.extend(&section.globals);
+            all.sections.extend(
+                section
+                    .sections
+                    .iter()
+                    .map(|(key, section)| (*key, section.clone())),
+            );
+        }
+        all
+    }
+
+    /// The valid key or section most similar to `key`.
+    fn similar_key(&self, key: &str) -> Option<&'static str> {
+        similar(
+            key,
+            self.keys
+                .iter()
+                .copied()
+                .chain(self.sections.iter().map(|(name, _)| *name))
+                .chain(self.defaults),
+        )
+    }
+}
+
+/**
+The raw sections of a subcommand config, which the config of each subcommand is deserialized from.
+
+They're kept to find the values of the global args of parent commands set in the section of the
+invoked subcommand, and the sections that don't match any subcommand, e.g. after a subcommand was
+renamed.
+*/
+#[doc(hidden)]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Sections(BTreeMap<String, Value>);
+
+impl Sections {
+    /// Merge with the sections of a higher precedence config layer.
+    pub fn merge(mut self, higher: Self) -> Self {
+        self.0.extend(higher.0);
+        self
+    }
+
+    /// Deserialize the section with config key `key`, which is `None` if it's missing or `null`.
+    pub fn section<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_json::Error> {
+        match self.0.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(section) => T::deserialize(section).map(Some),
+        }
+    }
+
+    /// The raw section with config key `key`.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.0.get_mut(key)
+    }
+
+    /// Add the keys of the shared defaults section that the section with config key `key`
+    /// doesn't set, so that global args can be set in the defaults too.
+    pub fn insert_defaults(&mut self, key: &str, defaults: &OtherKeys) {
+        let section = self
+            .0
+            .entry(key.to_owned())
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if let Some(section) = section.as_object_mut() {
+            for (key, value) in &defaults.0 {
+                section.entry(key).or_insert_with(|| value.clone());
+            }
+        }
+    }
+
+    /// The config keys of the sections that aren't in `known`, i.e. that don't match any
+    /// subcommand. Only mappings are sections, other unknown keys are left to strict loading.
+    pub fn unknown<'a>(&'a self, known: &'a [&str]) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(key, value)| value.is_object() && !known.contains(&key.as_str()))
+            .map(|(key, _)| key.as_str())
+    }
+}
+
+/**
+The keys of a config section that aren't fields of its config struct, e.g. the global args of a
+parent command, which are moved into the parent config before merging.
+
+Collected by a flattened field of the generated config structs.
+*/
+#[doc(hidden)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct OtherKeys(BTreeMap<String, Value>);
+
+impl OtherKeys {
+    /// Merge with the other keys of a higher precedence config layer.
+    pub fn merge(mut self, higher: Self) -> Self {
+        self.0.extend(higher.0);
+        self
+    }
+
+    /// Remove the value of `key`.
+    pub fn take(&mut self, key: &str) -> Option<Value> {
+        self.0.remove(key)
+    }
+}
+
+/// The candidate most similar to `key`, with the same cutoff as clap uses to suggest similar args.
+pub(crate) fn similar<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (strsim::jaro(key, candidate), candidate))
+        .filter(|(confidence, _)| *confidence > 0.7)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown config key '{}'", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n\n  tip: a similar key exists: '{suggestion}'")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! Tests that command aliases from the config are expanded before parsing.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(aliases_key)]
+pub struct Opts {
+    #[clap(long)]
+    verbose: bool,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    Deploy(DeployOptions),
+    Status,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct DeployOptions {
+    #[clap(long, default_value = "dev")]
+    env: String,
+
+    #[clap(long)]
+    confirm: bool,
+
+    #[clap(long)]
+    message: Option<String>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(aliases_key)]
+pub struct RequiredOpts {
+    #[clap(long)]
+    token: String,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+const CONFIG: &str = r#"
+verbose: true
+aliases:
+  deploy-prod: deploy --env prod --confirm
+  ship: deploy-prod --message 'first release'
+  status: deploy
+  loop-a: loop-b
+  loop-b: loop-a
+"#;
+
+fn parse(args: &[&str], config: &str) -> Result<Opts> {
+    Ok(Opts::try_parse_from_merged(
+        args,
+        Some(serde_yaml::from_str(config)?),
+    )?)
+}
+
+/// An alias is replaced by its args, which are merged with the config as usual.
+#[test]
+fn expand() -> Result<()> {
+    assert_eq!(
+        Opts {
+            verbose: true,
+            cmd: Some(SubCommand::Deploy(DeployOptions {
+                env: "prod".to_owned(),
+                confirm: true,
+                message: Some("extra".to_owned()),
+            })),
+        },
+        parse(&["myapp", "deploy-prod", "--message", "extra"], CONFIG)?
+    );
+    Ok(())
+}
+
+/// Aliases can expand to other aliases, and can quote args with spaces.
+#[test]
+fn nested() -> Result<()> {
+    let opts = parse(&["myapp", "ship"], CONFIG)?;
+    let Some(SubCommand::Deploy(deploy)) = opts.cmd else {
+        panic!("Expected the deploy subcommand.");
+    };
+    assert_eq!(Some("first release"), deploy.message.as_deref());
+    assert_eq!(
+        vec![
+            "myapp",
+            "deploy",
+            "--env",
+            "prod",
+            "--confirm",
+            "--message",
+            "first release"
+        ],
+        Opts::expand_aliases(["myapp", "ship"], Some(&serde_yaml::from_str(CONFIG)?))?
+    );
+    Ok(())
+}
+
+/// Required args can be set in the config alone.
+#[test]
+fn required_from_config() -> Result<()> {
+    let config = serde_yaml::from_str("token: secret\naliases:\n  st: status\n")?;
+    assert_eq!(
+        RequiredOpts {
+            token: "secret".to_owned(),
+            cmd: Some(SubCommand::Status),
+        },
+        RequiredOpts::try_parse_from_merged(["myapp", "st"], Some(config))?
+    );
+
+    let err = RequiredOpts::try_parse_from_merged(["myapp", "status"], None).unwrap_err();
+    assert_eq!(clap::error::ErrorKind::MissingRequiredArgument, err.kind());
+    Ok(())
+}
+
+/// Aliases only apply at the start of the args, and can't shadow subcommands.
+#[test]
+fn not_expanded() -> Result<()> {
+    assert_eq!(
+        Some(SubCommand::Status),
+        parse(&["myapp", "status"], CONFIG)?.cmd
+    );
+    assert_eq!(
+        vec!["myapp", "--verbose", "deploy-prod"],
+        Opts::expand_aliases(
+            ["myapp", "--verbose", "deploy-prod"],
+            Some(&serde_yaml::from_str(CONFIG)?)
+        )?
+    );
+    assert_eq!(
+        vec!["myapp", "deploy-prod"],
+        Opts::expand_aliases(["myapp", "deploy-prod"], None)?
+    );
+    Ok(())
+}
+
+/// Recursive and invalid aliases are reported instead of expanded.
+#[test]
+fn invalid() {
+    let err = parse(&["myapp", "loop-a"], CONFIG).unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "invalid alias 'loop-a' in config section aliases: it expands to itself: loop-a -> loop-b -> loop-a"
+        ),
+        "{err}"
+    );
+
+    let err = parse(
+        &["myapp", "bad"],
+        "aliases:\n  bad: deploy --message 'oops\n",
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "invalid alias 'bad' in config section aliases: it has an unterminated quote"
+        ),
+        "{err}"
+    );
+}
+
+/// Aliases are merged across config layers, and accepted by strict loading.
+#[test]
+fn layers_and_strict() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("aliases:\n  a: status\n  b: status\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("aliases:\n  b: deploy\n")?;
+    let config = lower.merge(higher);
+    assert_eq!(
+        vec!["myapp", "status"],
+        Opts::expand_aliases(["myapp", "a"], Some(&config))?
+    );
+    assert_eq!(
+        vec!["myapp", "deploy"],
+        Opts::expand_aliases(["myapp", "b"], Some(&config))?
+    );
+
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(CONFIG))?;
+    Ok(())
+}
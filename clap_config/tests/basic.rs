@@ -9,7 +9,7 @@ const FROM_DEFAULT: &str = "from-default";
 const FROM_ARG: &str = "from-arg";
 const FROM_CONFIG: &str = "from-config";
 
-#[derive(ClapConfig, Parser, Debug)]
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
 pub struct Opts {
     #[clap(long, default_value = FROM_DEFAULT)]
     flag: String,
@@ -57,3 +57,15 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches =
+        <Opts as CommandFactory>::command().get_matches_from(["myapp", "--flag", FROM_ARG]);
+    let opts = Opts::from_merged(matches, None);
+    let config = OptsConfig::from(&opts);
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
@@ -11,7 +11,7 @@ const FROM_DEFAULT: &str = "from-default";
 const FROM_ARG: &str = "from-arg";
 const FROM_CONFIG: &str = "from-config";
 
-#[derive(ClapConfig, Parser, Debug)]
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
 pub struct Opts {
     #[clap(subcommand)]
     pub(crate) cmd: Option<SubCommand>,
@@ -107,3 +107,19 @@ subcommand_a:
 
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from([
+        "myapp",
+        "subcommand-a",
+        "--flag-a",
+        FROM_ARG,
+    ]);
+    let opts = Opts::from_merged(matches, None);
+    let config = OptsConfig::from(&opts);
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp", "subcommand-a"]);
+    assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
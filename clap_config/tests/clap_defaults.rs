@@ -0,0 +1,91 @@
+//! Tests that a config can be built from the clap defaults.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap::ValueEnum;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value = "default")]
+    name: String,
+
+    #[clap(long)]
+    verbose: bool,
+
+    #[clap(long)]
+    proxy: Option<String>,
+
+    #[clap(long, default_values_t = vec!["a".to_owned(), "b".to_owned()])]
+    include: Vec<String>,
+
+    #[clap(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..100))]
+    port: u16,
+
+    #[clap(value_enum, long, default_value_t)]
+    color: Color,
+
+    #[clap_config(lockable)]
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    telemetry: bool,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum, PartialEq)]
+pub enum Color {
+    #[default]
+    Auto,
+    NeverEver,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    #[clap(long, default_value = "default-a")]
+    flag_a: String,
+
+    #[clap(long)]
+    flag_b: Option<String>,
+}
+
+/// Every default is filled in, including in subcommands, and values without a default are left
+/// out.
+#[test]
+fn from_clap_defaults() -> Result<()> {
+    let config = OptsConfig::from_clap_defaults();
+    let expected = "\
+name: default
+verbose: false
+include:
+- a
+- b
+port: '8'
+color: auto
+telemetry: true
+subcommand_a:
+  flag_a: default-a
+";
+    assert_eq!(expected, serde_yaml::to_string(&config)?);
+    Ok(())
+}
+
+/// Merging the defaults config gives the same values as no config.
+#[test]
+fn merge_defaults() -> Result<()> {
+    let args = ["myapp", "subcommand-a"];
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(args)?;
+    let expected = Opts::try_from_merged(matches, None)?;
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(args)?;
+    let opts = Opts::try_from_merged(matches, Some(OptsConfig::from_clap_defaults()))?;
+    assert_eq!(expected, opts);
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the tests.
+
+use clap_config::ParseWithConfig;
+use color_eyre::Result;
+
+/// Parse the command line `args`, starting with the binary name, and merge it with the YAML
+/// `config`.
+pub fn merge<T: ParseWithConfig>(args: &[&str], config: &str) -> Result<T> {
+    let matches = T::command().try_get_matches_from(args)?;
+    Ok(T::try_from_merged(
+        matches,
+        Some(serde_yaml::from_str(config)?),
+    )?)
+}
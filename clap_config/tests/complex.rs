@@ -52,7 +52,7 @@ const FLAG_BYTESIZE_CONFIG: &str = "20Gi";
 
 const FLAG_ENUM_DEFAULT: FlagEnum = FlagEnum::DefaultValue;
 const FLAG_ENUM_ARG: &str = "arg-value";
-const FLAG_ENUM_CONFIG: &str = "ConfigValue";
+const FLAG_ENUM_CONFIG: &str = "config-value";
 
 const POSITIONAL_STRING_ARG: &str = "positional-string-arg";
 
@@ -271,3 +271,17 @@ fn test_both_set() -> Result<()> {
     assert_eq!(expected_opts, opts);
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn test_round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(SET_ARGS);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    let matches = <Opts as CommandFactory>::command().get_matches_from(UNSET_ARGS);
+    let config: OptsConfig = serde_yaml::from_str(&config)?;
+    assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
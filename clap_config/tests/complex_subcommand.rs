@@ -53,7 +53,7 @@ const TOPLEVEL_FLAG_BYTESIZE_CONFIG: &str = "20Gi";
 
 const TOPLEVEL_FLAG_ENUM_DEFAULT: ToplevelFlagEnum = ToplevelFlagEnum::ToplevelDefaultValue;
 const TOPLEVEL_FLAG_ENUM_ARG: &str = "toplevel-arg-value";
-const TOPLEVEL_FLAG_ENUM_CONFIG: &str = "ToplevelConfigValue";
+const TOPLEVEL_FLAG_ENUM_CONFIG: &str = "toplevel-config-value";
 
 const TOPLEVEL_POSITIONAL_STRING_ARG: &str = "toplevel-positional-string-arg";
 
@@ -100,7 +100,7 @@ const SUBCOMMAND_A_FLAG_BYTESIZE_CONFIG: &str = "20Gi";
 const SUBCOMMAND_A_FLAG_ENUM_DEFAULT: SubcommandAFlagEnum =
     SubcommandAFlagEnum::SubcommandADefaultValue;
 const SUBCOMMAND_A_FLAG_ENUM_ARG: &str = "subcommand-a-arg-value";
-const SUBCOMMAND_A_FLAG_ENUM_CONFIG: &str = "SubcommandAConfigValue";
+const SUBCOMMAND_A_FLAG_ENUM_CONFIG: &str = "subcommand-a-config-value";
 
 const SUBCOMMAND_A_POSITIONAL_STRING_ARG: &str = "subcommand-a-positional-string-arg";
 
@@ -147,7 +147,7 @@ const SUBCOMMAND_B_FLAG_BYTESIZE_CONFIG: &str = "20Gi";
 const SUBCOMMAND_B_FLAG_ENUM_DEFAULT: SubcommandBFlagEnum =
     SubcommandBFlagEnum::SubcommandBDefaultValue;
 const SUBCOMMAND_B_FLAG_ENUM_ARG: &str = "subcommand-b-arg-value";
-const SUBCOMMAND_B_FLAG_ENUM_CONFIG: &str = "SubcommandBConfigValue";
+const SUBCOMMAND_B_FLAG_ENUM_CONFIG: &str = "subcommand-b-config-value";
 
 const SUBCOMMAND_B_POSITIONAL_STRING_ARG: &str = "subcommand-b-positional-string-arg";
 
@@ -856,3 +856,22 @@ fn test_both_set_subcommand_b() -> Result<()> {
     assert_eq!(expected_opts, opts);
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn test_round_trip() -> Result<()> {
+    for (set_args, unset_args) in [
+        (&SUBCOMMAND_A_SET_ARGS[..], &SUBCOMMAND_A_UNSET_ARGS[..]),
+        (&SUBCOMMAND_B_SET_ARGS[..], &SUBCOMMAND_B_UNSET_ARGS[..]),
+    ] {
+        let matches = <Opts as CommandFactory>::command().get_matches_from(set_args);
+        let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+        let opts = Opts::from_merged(matches, Some(config));
+
+        let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+        let matches = <Opts as CommandFactory>::command().get_matches_from(unset_args);
+        let config: OptsConfig = serde_yaml::from_str(&config)?;
+        assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    }
+    Ok(())
+}
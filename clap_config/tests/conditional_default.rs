@@ -0,0 +1,111 @@
+//! Tests that `default_value_if` and `default_value_ifs` are resolved with values from the config.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value = "slow")]
+    mode: String,
+
+    #[clap(long)]
+    fast: bool,
+
+    #[clap(long, default_value = "1", default_value_if("mode", "fast", "8"))]
+    threads: u32,
+
+    #[clap(long, default_value_ifs([("fast", "true", Some("fast")), ("mode", "slow", None)]))]
+    profile: Option<String>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct FastOpts {
+    #[clap(long, default_value = "fast")]
+    mode: String,
+
+    #[clap(long, default_value = "1", default_value_if("mode", "fast", "8"))]
+    threads: u32,
+
+    #[clap(long, default_value_if("mode", "fast", "turbo"))]
+    profile: Option<String>,
+}
+
+/// The condition isn't met anywhere, so the plain default is used.
+#[test]
+fn unset() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp"], "")?;
+    assert_eq!(1, opts.threads);
+    assert_eq!(None, opts.profile);
+    Ok(())
+}
+
+/// The condition is met on the command line.
+#[test]
+fn condition_in_args() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "--mode=fast", "--fast"], "")?;
+    assert_eq!(8, opts.threads);
+    assert_eq!(Some("fast".to_owned()), opts.profile);
+    Ok(())
+}
+
+/// The condition is met in the config.
+#[test]
+fn condition_in_config() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp"], "mode: fast\nfast: true")?;
+    assert_eq!(8, opts.threads);
+    assert_eq!(Some("fast".to_owned()), opts.profile);
+    Ok(())
+}
+
+/// The command line overrides the config value the condition depends on.
+#[test]
+fn condition_overridden_by_args() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "--mode=slow"], "mode: fast\nfast: false")?;
+    assert_eq!(1, opts.threads);
+    assert_eq!(None, opts.profile);
+    Ok(())
+}
+
+/// A default clap picked from the default of the controlling arg is undone when the config changes
+/// that arg.
+#[test]
+fn condition_on_default_changed_by_config() -> Result<()> {
+    assert_eq!(
+        FastOpts {
+            mode: "slow".to_owned(),
+            threads: 1,
+            profile: None,
+        },
+        common::merge::<FastOpts>(&["myapp"], "mode: slow")?
+    );
+    assert_eq!(
+        FastOpts {
+            mode: "fast".to_owned(),
+            threads: 8,
+            profile: Some("turbo".to_owned()),
+        },
+        common::merge::<FastOpts>(&["myapp"], "")?
+    );
+    Ok(())
+}
+
+/// An explicit value in the config beats the conditional default.
+#[test]
+fn value_in_config() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp"], "mode: fast\nthreads: 4")?;
+    assert_eq!(4, opts.threads);
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "--mode=fast"], "fast: true")?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, common::merge::<Opts>(&["myapp"], &config)?);
+    Ok(())
+}
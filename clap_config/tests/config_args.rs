@@ -0,0 +1,153 @@
+//! Tests that extra command line args can be listed in the config.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(args_key)]
+pub struct Opts {
+    #[clap(long)]
+    verbose: bool,
+
+    #[clap(long)]
+    name: Option<String>,
+
+    #[clap(long)]
+    color: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    Search(SearchOptions),
+    Status,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(args_key)]
+pub struct SearchOptions {
+    #[clap(short, long)]
+    smart_case: bool,
+
+    #[clap(short = 't', long = "type")]
+    types: Vec<String>,
+
+    #[clap(long, default_value = "10")]
+    max_count: u32,
+}
+
+const CONFIG: &str = "
+args: [--verbose, --color, always]
+color: never
+search:
+  args: [--smart-case, -t, rust, --max-count=5]
+  max_count: 20
+";
+
+fn parse(args: &[&str], config: &str) -> Result<Opts> {
+    Ok(Opts::try_parse_from_merged(
+        args,
+        Some(serde_yaml::from_str(config)?),
+    )?)
+}
+
+/// The args of each command are parsed like the command line, and win over the config values.
+#[test]
+fn config_args() -> Result<()> {
+    assert_eq!(
+        Opts {
+            verbose: true,
+            name: None,
+            color: Some("always".to_owned()),
+            cmd: Some(SubCommand::Search(SearchOptions {
+                smart_case: true,
+                types: vec!["rust".to_owned()],
+                max_count: 5,
+            })),
+        },
+        parse(&["myapp", "search"], CONFIG)?
+    );
+    Ok(())
+}
+
+/// The command line wins over the args from the config, and lists are appended to.
+#[test]
+fn command_line() -> Result<()> {
+    let opts = parse(
+        &[
+            "myapp",
+            "--color",
+            "auto",
+            "search",
+            "--type",
+            "toml",
+            "--max-count",
+            "1",
+        ],
+        CONFIG,
+    )?;
+    assert_eq!(Some("auto"), opts.color.as_deref());
+    assert_eq!(
+        Some(SubCommand::Search(SearchOptions {
+            smart_case: true,
+            types: vec!["rust".to_owned(), "toml".to_owned()],
+            max_count: 1,
+        })),
+        opts.cmd
+    );
+    Ok(())
+}
+
+/// Only the args set by the config can be repeated, the others are still rejected when they are
+/// given twice on the command line.
+#[test]
+fn repeated() -> Result<()> {
+    let opts = parse(&["myapp", "--verbose", "search", "-s"], CONFIG)?;
+    assert!(opts.verbose);
+
+    let err = parse(&["myapp", "--name", "a", "--name", "b"], CONFIG).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("the argument '--name <NAME>' cannot be used multiple times"),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// The subcommand is found after the values of options, and the args of other subcommands are
+/// left out.
+#[test]
+fn subcommand() -> Result<()> {
+    let opts = parse(&["myapp", "--name", "search", "status"], CONFIG)?;
+    assert_eq!(Some("search"), opts.name.as_deref());
+    assert_eq!(Some(SubCommand::Status), opts.cmd);
+    Ok(())
+}
+
+/// Args are appended across config layers, and invalid args are reported by clap.
+#[test]
+fn layers_and_invalid() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("args: [--name, lower]\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("args: [--name, higher, --verbose]\n")?;
+    let opts = Opts::try_parse_from_merged(["myapp"], Some(lower.merge(higher)))?;
+    assert_eq!(Some("higher"), opts.name.as_deref());
+    assert!(opts.verbose);
+
+    let err = parse(&["myapp"], "args: [--colour, always]\n").unwrap_err();
+    assert!(
+        err.to_string().contains("unexpected argument '--colour'"),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// Strict loading accepts the args keys.
+#[test]
+fn strict() -> Result<()> {
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(CONFIG))?;
+    Ok(())
+}
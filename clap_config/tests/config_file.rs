@@ -0,0 +1,123 @@
+//! Tests that the command line can be parsed and merged with a config file in one call.
+#![cfg(all(feature = "yaml", feature = "toml", feature = "json"))]
+
+use clap::ArgGroup;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::ParseWithConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value = "default")]
+    name: String,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+}
+
+#[derive(ClapConfig, clap::Args, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    #[clap(long, default_value = "80")]
+    port: u16,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap(group(ArgGroup::new("input").args(["file", "stdin"]).required(true)))]
+pub struct RequiredOpts {
+    #[clap(long)]
+    name: String,
+
+    #[clap(long)]
+    file: Option<String>,
+
+    #[clap(long)]
+    stdin: bool,
+}
+
+fn config_path(name: &str) -> String {
+    format!("{}/tests/config_file/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+fn parse(args: &[&str], name: &str) -> Result<Opts, clap::Error> {
+    Opts::try_parse_from_with_config(args, config_path(name))
+}
+
+/// The format is chosen by the extension of the config file.
+#[test]
+fn formats() -> Result<()> {
+    for (name, expected) in [
+        ("config.yaml", "yaml"),
+        ("config.yml", "yml"),
+        ("config.toml", "toml"),
+        ("config.json", "json"),
+    ] {
+        assert_eq!(expected, parse(&["myapp"], name)?.name, "{name}");
+    }
+    assert_eq!(
+        Opts {
+            name: "toml".to_owned(),
+            cmd: Some(SubCommand::SubcommandA(SubcommandAOptions { port: 8080 })),
+        },
+        parse(&["myapp", "subcommand-a"], "config.toml")?
+    );
+    Ok(())
+}
+
+/// The command line wins over the config file, and a missing config file is an empty config.
+#[test]
+fn command_line_and_missing() -> Result<()> {
+    assert_eq!(
+        "cli",
+        parse(&["myapp", "--name", "cli"], "config.yaml")?.name
+    );
+    assert_eq!("default", parse(&["myapp"], "missing.yaml")?.name);
+    Ok(())
+}
+
+/// Required args and groups can be set in the config file alone, and are still checked after
+/// merging.
+#[test]
+fn required() -> Result<()> {
+    assert_eq!(
+        RequiredOpts {
+            name: "config".to_owned(),
+            file: Some("in.txt".to_owned()),
+            stdin: false,
+        },
+        RequiredOpts::try_parse_from_with_config(["myapp"], config_path("required.yaml"))?
+    );
+
+    let err =
+        RequiredOpts::try_parse_from_with_config(["myapp", "--stdin"], config_path("missing.yaml"))
+            .unwrap_err();
+    assert_eq!(clap::error::ErrorKind::MissingRequiredArgument, err.kind());
+    assert!(err.to_string().contains("name"), "{err}");
+    Ok(())
+}
+
+/// Unsupported and invalid config files are reported with their path.
+#[test]
+fn errors() {
+    let err = parse(&["myapp"], "config.ini").unwrap_err().to_string();
+    assert!(
+        err.contains("unsupported format for config file ")
+            && err.contains("config.ini: expected one of the extensions [yaml, yml, toml, json]"),
+        "{err}"
+    );
+
+    let err = parse(&["myapp"], "invalid.yaml").unwrap_err().to_string();
+    assert!(
+        err.contains("invalid config file ") && err.contains("invalid.yaml: name: invalid type"),
+        "{err}"
+    );
+
+    let err = parse(&["myapp", "--nme", "cli"], "config.yaml").unwrap_err();
+    assert_eq!(clap::error::ErrorKind::UnknownArgument, err.kind());
+}
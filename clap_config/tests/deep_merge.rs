@@ -0,0 +1,100 @@
+//! Tests that `merge = "deep"` merges map fields key by key.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap_config(merge = "deep")]
+    #[clap(long, value_delimiter = ',', value_parser = parse_override)]
+    pub r#override: Vec<(String, String)>,
+}
+
+/// Parse a single `=`-separated key-value pair.
+fn parse_override(s: &str) -> Result<(String, String)> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .ok_or_else(|| eyre!("no `=` found in `{s}`"))
+}
+
+const CONFIG: &str = "
+override:
+  key1: config1
+  key2: config2
+  key3: config3
+";
+
+fn merge(args: &[&str]) -> Result<Vec<(String, String)>> {
+    let matches = <Opts as CommandFactory>::command()
+        .try_get_matches_from(std::iter::once("myapp").chain(args.iter().copied()))?;
+    let config: OptsConfig = serde_yaml::from_str(CONFIG)?;
+    Ok(Opts::try_from_merged(matches, Some(config))?.r#override)
+}
+
+fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+        .collect()
+}
+
+/// Args override single keys, and add new ones.
+#[test]
+fn override_keys() -> Result<()> {
+    let merged = merge(&["--override=key1=arg1,key4=arg4"])?;
+    let expected = pairs(&[
+        ("key1", "arg1"),
+        ("key2", "config2"),
+        ("key3", "config3"),
+        ("key4", "arg4"),
+    ]);
+    assert_eq!(expected, merged);
+    Ok(())
+}
+
+/// An empty value removes the key.
+#[test]
+fn remove_key() -> Result<()> {
+    let merged = merge(&["--override", "key3=", "--override", "key5="])?;
+    let expected = pairs(&[("key1", "config1"), ("key2", "config2")]);
+    assert_eq!(expected, merged);
+    Ok(())
+}
+
+/// An empty value in a config removes the key from lower layers, and isn't kept in a single config.
+#[test]
+fn remove_key_in_config() -> Result<()> {
+    let merge = |configs: &[&str]| -> Result<Vec<(String, String)>> {
+        let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+        let configs = configs
+            .iter()
+            .map(|config| Ok(("config".to_owned(), serde_yaml::from_str(config)?)))
+            .collect::<Result<Vec<(String, OptsConfig)>>>()?;
+        let (opts, _) = Opts::try_from_merged_with_sources(matches, configs)?;
+        Ok(opts.r#override)
+    };
+    let expected = pairs(&[("key1", "config1"), ("key2", "config2")]);
+    assert_eq!(expected, merge(&[CONFIG, "override:\n  key3: ''\n"])?);
+    assert_eq!(
+        pairs(&[("key1", "config1")]),
+        merge(&["override:\n  key1: config1\n  key2: ''\n"])?
+    );
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command()
+        .try_get_matches_from(["myapp", "--override=key1=arg1,key3="])?;
+    let config: OptsConfig = serde_yaml::from_str(CONFIG)?;
+    let opts = Opts::try_from_merged(matches, Some(config))?;
+    let config = OptsConfig::from(&opts);
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    assert_eq!(opts, Opts::try_from_merged(matches, Some(config))?);
+    Ok(())
+}
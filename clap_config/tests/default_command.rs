@@ -0,0 +1,188 @@
+//! Tests that the config can choose the subcommand to run when none is given.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::Source;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, global = true)]
+    verbose: bool,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(default_command_key)]
+pub enum SubCommand {
+    Serve(ServeOptions),
+    Migrate(MigrateOptions),
+    Status,
+    CheckAll,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct ServeOptions {
+    #[clap(long)]
+    port: u16,
+
+    #[clap(long, default_value = "localhost")]
+    host: String,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct MigrateOptions {
+    #[clap(subcommand)]
+    cmd: MigrateCommand,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(default_command_key = "run")]
+pub enum MigrateCommand {
+    Up,
+    Down,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct FlagOpts {
+    #[clap(long)]
+    default_command: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<FlagCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum FlagCommand {
+    Serve,
+}
+
+/// The default subcommand runs with the values from its section.
+#[test]
+fn default_command() -> Result<()> {
+    let config = "
+default_command: serve
+serve:
+  port: 8080
+  verbose: true
+";
+    assert_eq!(
+        Opts {
+            verbose: true,
+            cmd: Some(SubCommand::Serve(ServeOptions {
+                port: 8080,
+                host: "localhost".to_owned(),
+            })),
+        },
+        common::merge::<Opts>(&["myapp"], config)?
+    );
+
+    let opts = common::merge::<Opts>(&["myapp"], "default_command: status\n")?;
+    assert_eq!(Some(SubCommand::Status), opts.cmd);
+    Ok(())
+}
+
+/// A subcommand on the command line wins over the default, and without either there is none.
+#[test]
+fn command_line() -> Result<()> {
+    assert_eq!(
+        None,
+        common::merge::<Opts>(&["myapp"], "serve:\n  port: 8080\n")?.cmd
+    );
+    let opts = common::merge::<Opts>(&["myapp", "status"], "default_command: serve\n")?;
+    assert_eq!(Some(SubCommand::Status), opts.cmd);
+    Ok(())
+}
+
+/// Nested subcommands can have their own default under their own key, and are still required
+/// without one.
+#[test]
+fn nested() -> Result<()> {
+    let config = "
+default_command: migrate
+migrate:
+  run: down
+";
+    let opts = common::merge::<Opts>(&["myapp"], config)?;
+    assert_eq!(
+        Some(SubCommand::Migrate(MigrateOptions {
+            cmd: MigrateCommand::Down
+        })),
+        opts.cmd
+    );
+
+    let err = common::merge::<Opts>(&["myapp"], "default_command: migrate\n").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'migrate' requires a subcommand but one was not provided"),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// The default can be spelled like the subcommand name or like the key of its section.
+#[test]
+fn spelling() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp"], "default_command: check-all\n")?;
+    assert_eq!(Some(SubCommand::CheckAll), opts.cmd);
+    let opts = common::merge::<Opts>(&["myapp"], "default_command: check_all\n")?;
+    assert_eq!(Some(SubCommand::CheckAll), opts.cmd);
+    Ok(())
+}
+
+/// The default can come from a lower layer than the section of the subcommand.
+#[test]
+fn layers() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("default_command: serve\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("serve:\n  port: 8080\n  verbose: true\n")?;
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let (opts, sources) = Opts::try_from_merged_with_sources(
+        matches,
+        [("lower".to_owned(), lower), ("higher".to_owned(), higher)],
+    )?;
+    assert!(opts.verbose);
+    assert_eq!(
+        Some(&Source::Config("higher".to_owned())),
+        sources.get("serve.port")
+    );
+    Ok(())
+}
+
+/// Without `default_command_key`, the key is free for a field of the parent.
+#[test]
+fn not_enabled() -> Result<()> {
+    let matches = <FlagOpts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let opts = FlagOpts::try_from_merged(
+        matches,
+        Some(serde_yaml::from_str("default_command: serve\n")?),
+    )?;
+    assert_eq!(
+        FlagOpts {
+            default_command: Some("serve".to_owned()),
+            cmd: None,
+        },
+        opts
+    );
+    Ok(())
+}
+
+/// An unknown subcommand is reported with the most similar one.
+#[test]
+fn invalid() {
+    let err = common::merge::<Opts>(&["myapp"], "default_command: serv\n").unwrap_err();
+    let err = err.to_string();
+    assert!(
+        err.contains("invalid value 'serv' for config key default_command: no such subcommand"),
+        "{err}"
+    );
+    assert!(
+        err.contains("tip: a similar subcommand exists: 'serve'"),
+        "{err}"
+    );
+}
@@ -0,0 +1,166 @@
+//! Tests that a shared defaults section applies to every subcommand.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::Source;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, global = true)]
+    verbose: bool,
+
+    #[clap(subcommand)]
+    cmd: SubCommand,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(defaults_key)]
+pub enum SubCommand {
+    Deploy(DeployOptions),
+    Destroy(DestroyOptions),
+    Status,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct DeployOptions {
+    #[clap(long, default_value = "us-east-1")]
+    region: String,
+
+    #[clap(long)]
+    timeout: Option<u64>,
+
+    #[clap(long)]
+    image: Option<String>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct DestroyOptions {
+    #[clap(long, default_value = "us-east-1")]
+    region: String,
+
+    #[clap(long)]
+    timeout: Option<u64>,
+
+    #[clap(long)]
+    force: bool,
+}
+
+const CONFIG: &str = "
+defaults:
+  region: eu-west-1
+  timeout: 30
+  image: nginx
+deploy:
+  timeout: 60
+";
+
+/// The defaults fill the fields of every subcommand, and the subcommand section wins.
+#[test]
+fn defaults() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "deploy"], CONFIG)?;
+    assert_eq!(
+        SubCommand::Deploy(DeployOptions {
+            region: "eu-west-1".to_owned(),
+            timeout: Some(60),
+            image: Some("nginx".to_owned()),
+        }),
+        opts.cmd
+    );
+
+    // Keys of other subcommands, like `image`, are ignored.
+    let opts = common::merge::<Opts>(&["myapp", "destroy", "--region", "cli"], CONFIG)?;
+    assert_eq!(
+        SubCommand::Destroy(DestroyOptions {
+            region: "cli".to_owned(),
+            timeout: Some(30),
+            force: false,
+        }),
+        opts.cmd
+    );
+
+    assert_eq!(
+        SubCommand::Status,
+        common::merge::<Opts>(&["myapp", "status"], CONFIG)?.cmd
+    );
+    Ok(())
+}
+
+/// Global args of the parent command can be set in the defaults too, and the subcommand section
+/// wins.
+#[test]
+fn global_args() -> Result<()> {
+    let config = "defaults:\n  verbose: true\n";
+    assert!(common::merge::<Opts>(&["myapp", "deploy"], config)?.verbose);
+    let config = "defaults:\n  verbose: true\ndeploy:\n  verbose: false\n";
+    assert!(!common::merge::<Opts>(&["myapp", "deploy"], config)?.verbose);
+    assert!(!common::merge::<Opts>(&["myapp", "status"], "defaults:\n  verbose: true\n")?.verbose);
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(config))?;
+    Ok(())
+}
+
+/// The defaults only win over the subcommand section within their layer.
+#[test]
+fn layers() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("deploy:\n  region: lower\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("defaults:\n  region: higher\n")?;
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp", "deploy"])?;
+    let (opts, sources) = Opts::try_from_merged_with_sources(
+        matches,
+        [("lower".to_owned(), lower), ("higher".to_owned(), higher)],
+    )?;
+    let SubCommand::Deploy(deploy) = opts.cmd else {
+        panic!("Expected the deploy subcommand.");
+    };
+    assert_eq!("higher", deploy.region);
+    assert_eq!(
+        Some(&Source::Config("higher".to_owned())),
+        sources.get("deploy.region")
+    );
+    Ok(())
+}
+
+/// The defaults section is kept when converting to and from a config, and checked by strict
+/// loading.
+#[test]
+fn serialize_and_strict() -> Result<()> {
+    let config: OptsConfig = serde_yaml::from_str(CONFIG)?;
+    let expected = "\
+deploy:
+  timeout: 60
+defaults:
+  image: nginx
+  region: eu-west-1
+  timeout: 30
+";
+    assert_eq!(expected, serde_yaml::to_string(&config)?);
+
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(CONFIG))?;
+    let err = OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(
+        "defaults:\n  regon: a\n",
+    ))
+    .unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "unknown config key 'defaults.regon'\n\n  tip: a similar key exists: 'defaults.region'"
+        ),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// An invalid default is reported with the section key.
+#[test]
+fn invalid_default() {
+    let err =
+        common::merge::<Opts>(&["myapp", "deploy"], "defaults:\n  timeout: soon\n").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("invalid value in config section defaults"),
+        "{err}"
+    );
+}
@@ -0,0 +1,183 @@
+//! Tests that an example config can be generated from the clap definition.
+
+use clap::Parser;
+use clap::ValueEnum;
+use clap_config::ClapConfig;
+use clap_config::ExampleFormat;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    /// The name to greet.
+    #[clap(long, default_value = "world", env = "CLAP_CONFIG_TEST_EXAMPLE_NAME")]
+    name: String,
+
+    /// Only log errors.
+    #[clap(short, long)]
+    quiet: bool,
+
+    /**
+    The proxy to use.
+
+    Defaults to no proxy.
+    */
+    #[clap(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    #[clap(long, default_values_t = vec!["a b".to_owned(), "c".to_owned()])]
+    include: Vec<String>,
+
+    /// When to use colors.
+    #[clap(value_enum, long, default_value_t)]
+    color: Color,
+
+    #[clap(long, hide = true)]
+    secret: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum, PartialEq)]
+pub enum Color {
+    #[default]
+    Auto,
+    NeverEver,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    /// The port to listen on.
+    #[clap(long, default_value_t = 8080)]
+    port: u16,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct ThemeOpts {
+    #[clap(long, default_value = "always-on")]
+    theme: Theme,
+}
+
+/// A `ValueEnum` without `value_enum`, which the config deserializes with serde.
+#[derive(Debug, Clone, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    AlwaysOn,
+    Never,
+}
+
+const YAML: &str = r#"# The name to greet
+#
+# Default: world
+# Command line: --name
+# Env var: CLAP_CONFIG_TEST_EXAMPLE_NAME
+# name: world
+
+# Only log errors
+#
+# Default: false
+# Command line: -q, --quiet
+# quiet: false
+
+# The proxy to use.
+#
+# Defaults to no proxy.
+#
+# Command line: --proxy
+# proxy: <URL>
+
+# Default: a b, c
+# Command line: --include
+# include: ["a b", c]
+
+# When to use colors
+#
+# Default: auto
+# Possible values: auto, never-ever
+# Command line: --color
+# color: auto
+
+subcommand_a:
+  # The port to listen on
+  #
+  # Default: 8080
+  # Command line: --port
+  # port: 8080
+
+"#;
+
+/// Every key is documented and commented out, with a nested section for each subcommand.
+#[test]
+fn yaml() {
+    assert_eq!(YAML, OptsConfig::example_config(ExampleFormat::Yaml));
+}
+
+/// The TOML example uses a table for each subcommand.
+#[test]
+fn toml() {
+    let example = OptsConfig::example_config(ExampleFormat::Toml);
+    assert!(
+        example.contains("# include = [\"a b\", \"c\"]\n\n"),
+        "{example}"
+    );
+    assert!(
+        example.contains("\n[subcommand_a]\n# The port to listen on\n"),
+        "{example}"
+    );
+    assert!(example.ends_with("# port = 8080\n\n"), "{example}");
+}
+
+/// Values deserialized with serde are documented in the spelling the config accepts.
+#[test]
+fn serde_spelling() -> Result<()> {
+    let example = ThemeOptsConfig::example_config(ExampleFormat::Yaml);
+    assert_eq!(
+        "# Default: AlwaysOn\n# Possible values: AlwaysOn, Never\n# Command line: --theme\n\
+         # theme: AlwaysOn\n\n",
+        example
+    );
+    let config: ThemeOptsConfig = serde_yaml::from_str("theme: Never")?;
+    assert_eq!(Some(Theme::Never), config.theme);
+    Ok(())
+}
+
+/// Uncommenting the example values gives the clap defaults.
+#[test]
+fn uncommented() -> Result<()> {
+    // Uncomment the `key: value` lines, but not the `Default: ...` documentation lines.
+    let is_key = |key: &str| key.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    let uncomment = |example: String, separator: &str| {
+        example
+            .lines()
+            .map(|line| match line.trim_start().strip_prefix("# ") {
+                Some(value)
+                    if value
+                        .split_once(separator)
+                        .is_some_and(|(key, value)| is_key(key) && !value.starts_with('<')) =>
+                {
+                    format!("{}{value}", &line[..line.len() - line.trim_start().len()])
+                }
+                _ => line.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let yaml = uncomment(OptsConfig::example_config(ExampleFormat::Yaml), ": ");
+    let config: OptsConfig = serde_yaml::from_str(&yaml)?;
+    let expected = serde_yaml::to_string(&OptsConfig::from_clap_defaults())?;
+    assert_eq!(expected, serde_yaml::to_string(&config)?);
+
+    let toml = uncomment(OptsConfig::example_config(ExampleFormat::Toml), " = ");
+    let config: OptsConfig = toml::from_str(&toml)?;
+    assert_eq!(expected, serde_yaml::to_string(&config)?);
+    Ok(())
+}
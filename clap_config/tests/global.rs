@@ -0,0 +1,167 @@
+//! Tests that global args can be set in the config of any subcommand.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::Source;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, global = true)]
+    verbose: bool,
+
+    #[clap(long, global = true)]
+    level: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB(SubcommandBOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    #[clap(long)]
+    flag_a: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<NestedSubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandBOptions {
+    #[clap(long)]
+    flag_b: Option<String>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum NestedSubCommand {
+    Nested(NestedOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct NestedOptions {
+    #[clap(long)]
+    flag_nested: Option<String>,
+}
+
+const CONFIG: &str = "
+level: top
+subcommand_a:
+  level: subcommand_a
+  nested:
+    verbose: true
+    level: nested
+subcommand_b:
+  level: subcommand_b
+";
+
+/// The section of the invoked subcommand wins over the top level.
+#[test]
+fn subcommand_section() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "subcommand-a"], CONFIG)?;
+    assert_eq!(Some("subcommand_a"), opts.level.as_deref());
+    assert!(!opts.verbose);
+
+    let opts = common::merge::<Opts>(&["myapp", "subcommand-b"], CONFIG)?;
+    assert_eq!(Some("subcommand_b"), opts.level.as_deref());
+    Ok(())
+}
+
+/// The most nested section wins.
+#[test]
+fn nested_section() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "subcommand-a", "nested"], CONFIG)?;
+    assert_eq!(Some("nested"), opts.level.as_deref());
+    assert!(opts.verbose);
+    Ok(())
+}
+
+/// The top level value is used without a subcommand, and the command line still wins.
+#[test]
+fn top_level_and_command_line() -> Result<()> {
+    assert_eq!(
+        Some("top"),
+        common::merge::<Opts>(&["myapp"], CONFIG)?.level.as_deref()
+    );
+    let opts = common::merge::<Opts>(
+        &["myapp", "subcommand-a", "nested", "--level", "cli"],
+        CONFIG,
+    )?;
+    assert_eq!(Some("cli"), opts.level.as_deref());
+    Ok(())
+}
+
+/// Subcommand sections only win within their layer, and are recorded as coming from it.
+#[test]
+fn layers() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("subcommand_a:\n  level: lower\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("level: higher\n")?;
+    let matches =
+        <Opts as CommandFactory>::command().try_get_matches_from(["myapp", "subcommand-a"])?;
+    let (opts, sources) = Opts::try_from_merged_with_sources(
+        matches,
+        [("lower".to_owned(), lower), ("higher".to_owned(), higher)],
+    )?;
+    assert_eq!(Some("higher"), opts.level.as_deref());
+    let origin = sources.origin("level").unwrap();
+    assert_eq!(Source::Config("higher".to_owned()), origin.source);
+    assert_eq!(
+        vec![(Source::Config("lower".to_owned()), vec!["lower".to_owned()])],
+        origin.shadowed
+    );
+    Ok(())
+}
+
+/// Strict loading accepts global args in subcommand sections.
+#[test]
+fn strict() -> Result<()> {
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(CONFIG))?;
+    let err = OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(
+        "subcommand_b:\n  flag_a: a\n",
+    ))
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unknown config key 'subcommand_b.flag_a'"),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// An invalid value in a subcommand section is reported with its key.
+#[test]
+fn invalid_value() -> Result<()> {
+    let err = common::merge::<Opts>(
+        &["myapp", "subcommand-b"],
+        "subcommand_b:\n  verbose: [1]\n",
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("invalid value for config key verbose in a subcommand section"),
+        "{err}"
+    );
+    Ok(())
+}
+
+/// Configs without subcommands don't collect unknown keys, so they can be built with a struct
+/// literal, and errors keep their location.
+#[test]
+fn leaf_config() {
+    let config = SubcommandBOptionsConfig {
+        flag_b: Some("b".to_owned()),
+    };
+    assert_eq!(Some("b"), config.flag_b.as_deref());
+
+    let err = serde_yaml::from_str::<SubcommandBOptionsConfig>("\nflag_b: [1]\n").unwrap_err();
+    assert!(err.to_string().contains("line 2 column 9"), "{err}");
+}
@@ -0,0 +1,199 @@
+//! Tests that multiple config layers (e.g. system, user, and project configs) are merged in order.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use std::collections::BTreeMap;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value = "default")]
+    name: String,
+
+    #[clap_config(merge = "append")]
+    #[clap(long)]
+    include: Vec<String>,
+
+    #[clap_config(lockable)]
+    #[clap(long)]
+    registry: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    #[clap(long, default_value = "default-a")]
+    flag_a: String,
+
+    #[clap(long, default_value = "default-b")]
+    flag_b: String,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommandACmd>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommandACmd {
+    SubcommandX(SubcommandXOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandXOptions {
+    #[clap(long, default_value = "default-x")]
+    flag_x: String,
+
+    #[clap_config(merge = "deep")]
+    #[clap(long, value_parser = parse_override)]
+    r#override: Vec<(String, String)>,
+}
+
+/// Parse a single `=`-separated key-value pair.
+fn parse_override(s: &str) -> Result<(String, String)> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .ok_or_else(|| color_eyre::eyre::eyre!("no `=` found in `{s}`"))
+}
+
+const SYSTEM_CONFIG: &str = "
+name: system
+include: [system]
+registry:
+  value: https://registry.internal
+  locked: true
+subcommand_a:
+  flag_a: system-a
+  flag_b: system-b
+  subcommand_x:
+    flag_x: system-x
+    override:
+      key1: system1
+      key2: system2
+";
+
+const USER_CONFIG: &str = "
+include: [user]
+registry: https://example.com
+subcommand_a:
+  flag_a: user-a
+";
+
+const PROJECT_CONFIG: &str = "
+name: project
+subcommand_a:
+  subcommand_x:
+    override:
+      key2: project2
+      key3: project3
+";
+
+fn layers() -> Result<Vec<OptsConfig>> {
+    Ok(vec![
+        serde_yaml::from_str(SYSTEM_CONFIG)?,
+        serde_yaml::from_str(USER_CONFIG)?,
+        serde_yaml::from_str(PROJECT_CONFIG)?,
+    ])
+}
+
+/// Later layers override earlier ones, recursing into nested subcommand configs.
+#[test]
+fn merge_layers() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from([
+        "myapp",
+        "subcommand-a",
+        "subcommand-x",
+    ])?;
+    let opts = Opts::try_from_merged_layers(matches, layers()?)?;
+
+    let expected = Opts {
+        name: "project".to_owned(),
+        include: vec!["system".to_owned(), "user".to_owned()],
+        registry: Some("https://registry.internal".to_owned()),
+        cmd: Some(SubCommand::SubcommandA(SubcommandAOptions {
+            flag_a: "user-a".to_owned(),
+            flag_b: "system-b".to_owned(),
+            cmd: Some(SubCommandACmd::SubcommandX(SubcommandXOptions {
+                flag_x: "system-x".to_owned(),
+                r#override: vec![
+                    ("key1".to_owned(), "system1".to_owned()),
+                    ("key2".to_owned(), "project2".to_owned()),
+                    ("key3".to_owned(), "project3".to_owned()),
+                ],
+            })),
+        })),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// The command line still takes precedence over all the layers.
+#[test]
+fn args_over_layers() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from([
+        "myapp",
+        "--name=cli",
+        "subcommand-a",
+        "--flag-a=cli-a",
+    ])?;
+    let opts = Opts::try_from_merged_layers(matches, layers()?)?;
+    assert_eq!("cli", opts.name);
+    let Some(SubCommand::SubcommandA(a)) = opts.cmd else {
+        panic!("Expected subcommand A, got {:?}", opts.cmd);
+    };
+    assert_eq!("cli-a", a.flag_a);
+    assert_eq!("system-b", a.flag_b);
+    Ok(())
+}
+
+/// The merged config can be used directly too.
+#[test]
+fn merge_configs() -> Result<()> {
+    let merged = layers()?
+        .into_iter()
+        .reduce(OptsConfig::merge)
+        .unwrap_or_default();
+    let x = merged
+        .cmd
+        .and_then(|cmd| cmd.subcommand_a)
+        .and_then(|a| a.cmd)
+        .and_then(|cmd| cmd.subcommand_x)
+        .and_then(|x| x.r#override);
+    let expected = BTreeMap::from([
+        ("key1".to_owned(), "system1".to_owned()),
+        ("key2".to_owned(), "project2".to_owned()),
+        ("key3".to_owned(), "project3".to_owned()),
+    ]);
+    assert_eq!(Some(expected), x);
+    Ok(())
+}
+
+/// No layers is the same as no config.
+#[test]
+fn no_layers() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let opts = Opts::try_from_merged_layers(matches, [])?;
+    assert_eq!("default", opts.name);
+    assert_eq!(None, opts.cmd);
+    Ok(())
+}
+
+/// Converting the merged struct back into a single config gives the same values as the layers.
+#[test]
+fn round_trip() -> Result<()> {
+    let args = ["myapp", "subcommand-a", "subcommand-x"];
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(args)?;
+    let opts = Opts::try_from_merged_layers(matches, layers()?)?;
+    let config = OptsConfig::from(&opts);
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(args)?;
+    assert_eq!(opts, Opts::try_from_merged(matches, Some(config))?);
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Tests that locked config values can't be overridden by the command line or env vars.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::Lockable;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap_config(lockable)]
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    telemetry: bool,
+
+    #[clap_config(lockable)]
+    #[clap(long)]
+    registry: Option<String>,
+
+    #[clap_config(lockable)]
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..))]
+    port: Option<u16>,
+
+    #[clap(long)]
+    name: Option<String>,
+}
+
+const LOCKED_CONFIG: &str = "
+telemetry:
+  value: false
+  locked: true
+registry:
+  value: https://registry.internal
+  locked: true
+port:
+  value: 8080
+  locked: true
+name: config
+";
+
+/// Locked values are used, and the values that aren't locked can still be overridden.
+#[test]
+fn locked() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "--name=cli"], LOCKED_CONFIG)?;
+    let expected = Opts {
+        telemetry: false,
+        registry: Some("https://registry.internal".to_owned()),
+        port: Some(8080),
+        name: Some("cli".to_owned()),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Overriding a locked value on the command line is an error.
+#[test]
+fn overridden() -> Result<()> {
+    const ERROR: &str = "'--port <PORT>' can't be used, as config key port is locked";
+
+    let err =
+        common::merge::<Opts>(&["myapp", "--port=1", "--name=cli"], LOCKED_CONFIG).unwrap_err();
+    assert!(err.to_string().contains(ERROR), "{err}");
+
+    let config: OptsConfig = serde_yaml::from_str(LOCKED_CONFIG)?;
+    let matches =
+        <Opts as CommandFactory>::command().try_get_matches_from(["myapp", "--port=1"])?;
+    let err = Opts::try_from_merged_layers(matches, [config]).unwrap_err();
+    assert_eq!(clap::error::ErrorKind::ArgumentConflict, err.kind());
+    assert!(err.to_string().contains(ERROR), "{err}");
+    Ok(())
+}
+
+/// Overriding a locked value is also an error when parsing with a config file.
+#[cfg(feature = "yaml")]
+#[test]
+fn overridden_with_config_file() {
+    use clap_config::ParseWithConfig;
+
+    let path = format!(
+        "{}/tests/config_file/locked.yaml",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let err = Opts::try_parse_from_with_config(["myapp", "--telemetry=true"], path).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'--telemetry <TELEMETRY>' can't be used, as config key telemetry is locked"),
+        "{err}"
+    );
+}
+
+/// Values that aren't locked can still be overridden, whichever form they're written in.
+#[test]
+fn not_locked() -> Result<()> {
+    let opts = common::merge::<Opts>(
+        &[
+            "myapp",
+            "--telemetry=true",
+            "--registry=https://example.com",
+        ],
+        "
+telemetry: false
+registry:
+  value: https://registry.internal
+  locked: false
+",
+    )?;
+    assert_eq!(true, opts.telemetry);
+    assert_eq!(Some("https://example.com".to_owned()), opts.registry);
+    Ok(())
+}
+
+/// The config struct can be built in code too.
+#[test]
+fn config_struct() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let config = OptsConfig {
+        telemetry: Some(Lockable::Locked {
+            value: false,
+            locked: true,
+        }),
+        registry: Some("https://registry.internal".to_owned().into()),
+        ..Default::default()
+    };
+    let opts = Opts::try_from_merged(matches, Some(config))?;
+    assert_eq!(false, opts.telemetry);
+    assert_eq!(Some("https://registry.internal".to_owned()), opts.registry);
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp", "--name=cli"], LOCKED_CONFIG)?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, common::merge::<Opts>(&["myapp"], &config)?);
+    Ok(())
+}
@@ -14,7 +14,7 @@ use color_eyre::Result;
 use const_format::formatcp;
 use pretty_assertions::assert_eq;
 
-#[derive(ClapConfig, Parser, Debug)]
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
 pub struct Opts {
     /**
     Override a variable to a different value.
@@ -122,3 +122,15 @@ fn set_both() -> Result<()> {
     assert_eq!(expected, opts.r#override);
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(SET_ARGS);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+    let config = OptsConfig::from(&opts);
+    let matches = <Opts as CommandFactory>::command().get_matches_from(UNSET_ARGS);
+    assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Tests for the `merge` strategies of `Vec` fields.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(merge = "append")]
+pub struct Opts {
+    #[clap(long)]
+    include: Vec<String>,
+
+    #[clap_config(merge = "prepend")]
+    #[clap(long)]
+    plugin: Vec<String>,
+
+    #[clap_config(merge = "replace")]
+    #[clap(long)]
+    header: Vec<String>,
+}
+
+const CONFIG: &str = "
+include: [config-include]
+plugin: [config-plugin]
+header: [config-header]
+";
+
+/// Values only in the config are used as-is.
+#[test]
+fn config_only() -> Result<()> {
+    let opts = common::merge::<Opts>(&["myapp"], CONFIG)?;
+    let expected = Opts {
+        include: vec!["config-include".to_owned()],
+        plugin: vec!["config-plugin".to_owned()],
+        header: vec!["config-header".to_owned()],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Args are appended to, prepended to, or replace the config values.
+#[test]
+fn both_set() -> Result<()> {
+    let opts = common::merge::<Opts>(
+        &[
+            "myapp",
+            "--include=arg-include",
+            "--plugin=arg-plugin",
+            "--header=arg-header",
+        ],
+        CONFIG,
+    )?;
+    let expected = Opts {
+        include: vec!["config-include".to_owned(), "arg-include".to_owned()],
+        plugin: vec!["arg-plugin".to_owned(), "config-plugin".to_owned()],
+        header: vec!["arg-header".to_owned()],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Args are used as-is when the config doesn't set the field.
+#[test]
+fn args_only() -> Result<()> {
+    let opts = common::merge::<Opts>(
+        &["myapp", "--include=arg-include", "--plugin=arg-plugin"],
+        "",
+    )?;
+    let expected = Opts {
+        include: vec!["arg-include".to_owned()],
+        plugin: vec!["arg-plugin".to_owned()],
+        header: vec![],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = common::merge::<Opts>(
+        &["myapp", "--include=arg-include", "--plugin=arg-plugin"],
+        CONFIG,
+    )?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, common::merge::<Opts>(&["myapp"], &config)?);
+    Ok(())
+}
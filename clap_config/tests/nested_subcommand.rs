@@ -11,6 +11,7 @@ const FROM_ARG_A: &str = "from-arg-a";
 const FROM_CONFIG_A: &str = "from-config-a";
 
 const FROM_DEFAULT_C: &str = "from-default-c";
+const FROM_ARG_C: &str = "from-arg-c";
 const FROM_CONFIG_C: &str = "from-config-c";
 
 const FROM_DEFAULT_X: &str = "from-default-x";
@@ -70,6 +71,21 @@ const SET_ARGS_B: [&str; 2] = ["myapp", "subcommand-b"];
 const UNSET_ARGS_X: [&str; 3] = ["myapp", "subcommand-a", "subcommand-x"];
 const UNSET_ARGS_Y: [&str; 3] = ["myapp", "subcommand-a", "subcommand-y"];
 const SET_ARGS_Z: [&str; 3] = ["myapp", "subcommand-c", "subcommand-z"];
+const UNSET_ARGS_Z: [&str; 3] = ["myapp", "subcommand-c", "subcommand-z"];
+const SET_ARGS_Y: [&str; 5] = [
+    "myapp",
+    "subcommand-a",
+    "--flag-a",
+    FROM_ARG_A,
+    "subcommand-y",
+];
+const SET_ARGS_C: [&str; 5] = [
+    "myapp",
+    "subcommand-c",
+    "--flag-c",
+    FROM_ARG_C,
+    "subcommand-z",
+];
 const SET_ARGS_X: [&str; 7] = [
     "myapp",
     "subcommand-a",
@@ -319,3 +335,22 @@ fn nested_unit_nonoptional_subcommand() -> Result<()> {
 
     Ok(())
 }
+
+/// Converting the merged struct back into a config and merging that with args that only choose the
+/// subcommands gives the same values, including for nested and unit subcommands.
+#[test]
+fn round_trip() -> Result<()> {
+    for (set_args, unset_args) in [
+        (&SET_ARGS_X[..], &UNSET_ARGS_X[..]),
+        (&SET_ARGS_Y[..], &UNSET_ARGS_Y[..]),
+        (&SET_ARGS_C[..], &UNSET_ARGS_Z[..]),
+    ] {
+        let matches = <Opts as CommandFactory>::command().get_matches_from(set_args);
+        let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+        let opts = Opts::from_merged(matches, Some(config));
+        let config = OptsConfig::from(&opts);
+        let matches = <Opts as CommandFactory>::command().get_matches_from(unset_args);
+        assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    }
+    Ok(())
+}
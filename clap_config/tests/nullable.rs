@@ -0,0 +1,110 @@
+//! Tests that an explicit `null` in a config layer resets the value set by a lower layer.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap::ValueEnum;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(nullable)]
+pub struct Opts {
+    #[clap(long)]
+    proxy: Option<String>,
+
+    #[clap(long, default_value_t = 80)]
+    port: u16,
+
+    #[clap(value_enum, long, default_value_t)]
+    color: Color,
+
+    #[clap_config(merge = "append")]
+    #[clap(long)]
+    include: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum, PartialEq)]
+pub enum Color {
+    #[default]
+    Auto,
+    Never,
+}
+
+const SYSTEM_CONFIG: &str = "
+proxy: http://proxy.internal
+port: 8080
+color: never
+include: [system]
+";
+
+fn merge(layers: &[&str]) -> Result<Opts> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let layers = layers
+        .iter()
+        .map(|layer| serde_yaml::from_str(layer))
+        .collect::<Result<Vec<OptsConfig>, _>>()?;
+    Ok(Opts::try_from_merged_layers(matches, layers)?)
+}
+
+/// A missing key keeps the value from the lower layer.
+#[test]
+fn missing_key() -> Result<()> {
+    let opts = merge(&[SYSTEM_CONFIG, "include: [user]"])?;
+    let expected = Opts {
+        proxy: Some("http://proxy.internal".to_owned()),
+        port: 8080,
+        color: Color::Never,
+        include: vec!["system".to_owned(), "user".to_owned()],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// An explicit `null` resets the value to the clap default.
+#[test]
+fn explicit_null() -> Result<()> {
+    let opts = merge(&[
+        SYSTEM_CONFIG,
+        "
+proxy: null
+port: null
+color: ~
+include: null
+",
+    ])?;
+    let expected = Opts {
+        proxy: None,
+        port: 80,
+        color: Color::Auto,
+        include: vec![],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// A value after an explicit `null` is used as normal.
+#[test]
+fn null_then_value() -> Result<()> {
+    let opts = merge(&[SYSTEM_CONFIG, "include: null", "include: [project]"])?;
+    assert_eq!(vec!["project".to_owned()], opts.include);
+    Ok(())
+}
+
+/// The config struct tells a missing key and an explicit `null` apart.
+#[test]
+fn config_struct() -> Result<()> {
+    let config: OptsConfig = serde_yaml::from_str("proxy: null")?;
+    assert_eq!(Some(None), config.proxy);
+    assert_eq!(None, config.port);
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = merge(&[SYSTEM_CONFIG, "proxy: null\ninclude: [user]"])?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, merge(&[&config])?);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Tests that in `opt_in` mode only fields marked `include` are read from the config.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+const FROM_DEFAULT: &str = "from-default";
+const FROM_ARG: &str = "from-arg";
+const FROM_CONFIG: &str = "from-config";
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(opt_in)]
+pub struct Opts {
+    #[clap_config(include)]
+    #[clap(long, default_value = FROM_DEFAULT)]
+    flag: String,
+
+    #[clap(long)]
+    unsafe_allow_root: bool,
+
+    #[clap(long, default_value = FROM_DEFAULT)]
+    not_included_flag: String,
+}
+
+const SET_CONFIG: &str = "
+flag: from-config
+unsafe_allow_root: true
+not_included_flag: from-config
+";
+
+/// Fields without `include` are ignored even if they are set in the config.
+#[test]
+fn only_included_fields_read_from_config() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let expected = Opts {
+        flag: FROM_CONFIG.to_owned(),
+        unsafe_allow_root: false,
+        not_included_flag: FROM_DEFAULT.to_owned(),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Fields without `include` can still be set via args.
+#[test]
+fn not_included_fields_set_from_args() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from([
+        "myapp",
+        "--unsafe-allow-root",
+        "--not-included-flag",
+        FROM_ARG,
+    ]);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let expected = Opts {
+        flag: FROM_CONFIG.to_owned(),
+        unsafe_allow_root: true,
+        not_included_flag: FROM_ARG.to_owned(),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Only included fields are generated in the config struct.
+#[test]
+fn config_struct_only_has_included_fields() {
+    let config = OptsConfig {
+        flag: Some(FROM_CONFIG.to_owned()),
+    };
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let opts = Opts::from_merged(matches, Some(config));
+    assert_eq!(FROM_CONFIG, opts.flag);
+}
+
+/// Only the included fields are converted back into the config.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from([
+        "myapp",
+        "--flag",
+        FROM_ARG,
+        "--unsafe-allow-root",
+    ]);
+    let opts = Opts::from_merged(matches, None);
+    let config = OptsConfig::from(&opts);
+    assert_eq!("flag: from-arg\n", serde_yaml::to_string(&config)?);
+
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let expected = Opts {
+        flag: FROM_ARG.to_owned(),
+        unsafe_allow_root: false,
+        not_included_flag: FROM_DEFAULT.to_owned(),
+    };
+    assert_eq!(expected, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Tests that non-serde fields can be read from the config by running them through their clap
+//! value parser.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+struct NonSerializable(bool);
+
+impl Display for NonSerializable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for NonSerializable {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let inner: bool = s.parse()?;
+        Ok(Self(inner))
+    }
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap_config(parse)]
+    #[clap(long, default_value_t = NonSerializable(false))]
+    flag: NonSerializable,
+
+    #[clap_config(parse)]
+    #[clap(long)]
+    flag_option: Option<NonSerializable>,
+
+    #[clap_config(parse)]
+    #[clap(long)]
+    flag_vec: Vec<NonSerializable>,
+}
+
+const SET_CONFIG: &str = "
+flag: 'true'
+flag_option: 'true'
+flag_vec: ['true', 'false']
+";
+
+/// Nothing set anywhere, use the defaults.
+#[test]
+fn unset() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str("")?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let expected = Opts {
+        flag: NonSerializable(false),
+        flag_option: None,
+        flag_vec: vec![],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Values from the config are parsed with the value parser.
+#[test]
+fn set_config() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let expected = Opts {
+        flag: NonSerializable(true),
+        flag_option: Some(NonSerializable(true)),
+        flag_vec: vec![NonSerializable(true), NonSerializable(false)],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Args still take precedence over the config.
+#[test]
+fn set_both() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from([
+        "myapp",
+        "--flag=false",
+        "--flag-option=false",
+        "--flag-vec=false",
+    ]);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+
+    let expected = Opts {
+        flag: NonSerializable(false),
+        flag_option: Some(NonSerializable(false)),
+        flag_vec: vec![NonSerializable(false)],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Invalid config values are reported with the config key.
+#[test]
+fn invalid_config() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str("flag: not-a-bool")?;
+    let err = Opts::try_from_merged(matches, Some(config)).unwrap_err();
+
+    assert_eq!(clap::error::ErrorKind::ValueValidation, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("invalid value 'not-a-bool' for 'config key flag'"),
+        "{message}"
+    );
+    Ok(())
+}
+
+/// Parsed fields are converted back into their raw config values.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str(SET_CONFIG)?;
+    let opts = Opts::from_merged(matches, Some(config));
+    let config = OptsConfig::from(&opts);
+    assert_eq!(
+        Some(vec!["true".to_owned(), "false".to_owned()]),
+        config.flag_vec
+    );
+
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    assert_eq!(opts, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
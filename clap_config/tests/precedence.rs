@@ -0,0 +1,97 @@
+//! Tests that the precedence of the config relative to env vars and defaults can be changed.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use std::sync::Once;
+
+mod common;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(precedence = "cli, config, env, default")]
+pub struct Opts {
+    #[clap(long, env = "CLAP_CONFIG_TEST_PRECEDENCE_CONFIG_FIRST")]
+    config_first: String,
+
+    #[clap_config(precedence = "cli, env, config, default")]
+    #[clap(long, env = "CLAP_CONFIG_TEST_PRECEDENCE_ENV_FIRST")]
+    env_first: String,
+
+    #[clap_config(precedence = "cli, env, default, config")]
+    #[clap(long, default_value = "default")]
+    default_first: String,
+}
+
+static SET_ENV: Once = Once::new();
+
+fn merge(args: &[&str], config: &str) -> Result<Opts> {
+    SET_ENV.call_once(|| {
+        // SAFETY: all the tests in this file set the env vars through this `Once` before reading
+        // them.
+        unsafe {
+            std::env::set_var("CLAP_CONFIG_TEST_PRECEDENCE_CONFIG_FIRST", "env");
+            std::env::set_var("CLAP_CONFIG_TEST_PRECEDENCE_ENV_FIRST", "env");
+        }
+    });
+    common::merge(args, config)
+}
+
+/// The config beats env vars or defaults, depending on the precedence.
+#[test]
+fn set_config() -> Result<()> {
+    let opts = merge(
+        &["myapp"],
+        "config_first: config\nenv_first: config\ndefault_first: config",
+    )?;
+    let expected = Opts {
+        config_first: "config".to_owned(),
+        env_first: "env".to_owned(),
+        default_first: "default".to_owned(),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// The command line beats the config.
+#[test]
+fn set_args() -> Result<()> {
+    let opts = merge(
+        &[
+            "myapp",
+            "--config-first=cli",
+            "--env-first=cli",
+            "--default-first=cli",
+        ],
+        "config_first: config\nenv_first: config\ndefault_first: config",
+    )?;
+    let expected = Opts {
+        config_first: "cli".to_owned(),
+        env_first: "cli".to_owned(),
+        default_first: "cli".to_owned(),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Env vars are still used when the config doesn't set the value.
+#[test]
+fn unset_config() -> Result<()> {
+    let opts = merge(&["myapp"], "")?;
+    let expected = Opts {
+        config_first: "env".to_owned(),
+        env_first: "env".to_owned(),
+        default_first: "default".to_owned(),
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = merge(&["myapp"], "config_first: config")?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, merge(&["myapp"], &config)?);
+    Ok(())
+}
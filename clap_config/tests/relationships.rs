@@ -0,0 +1,115 @@
+//! Tests that `conflicts_with`, `requires`, and `ArgGroup` rules are checked after merging the
+//! config.
+
+use clap::error::ErrorKind;
+use clap::ArgGroup;
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap(group(ArgGroup::new("input").args(["file", "stdin"]).required(true)))]
+pub struct Opts {
+    #[clap(long, conflicts_with = "format")]
+    json: bool,
+
+    #[clap(long)]
+    format: Option<String>,
+
+    #[clap(long, requires = "password")]
+    user: Option<String>,
+
+    #[clap(long)]
+    password: Option<String>,
+
+    #[clap(long)]
+    file: Option<String>,
+
+    #[clap(long)]
+    stdin: bool,
+}
+
+fn try_merge(args: &[&str], config: &str) -> Result<Opts, clap::Error> {
+    let matches = Opts::relax_command(<Opts as CommandFactory>::command()).try_get_matches_from(
+        std::iter::once("myapp").chain(args.iter().copied()),
+    )?;
+    let config: OptsConfig = serde_yaml::from_str(config).unwrap();
+    Opts::try_from_merged(matches, Some(config))
+}
+
+/// Requirements can be satisfied by the config.
+#[test]
+fn satisfied_by_config() -> Result<()> {
+    let opts = try_merge(&["--user=me"], "password: hunter2\nfile: in.txt")?;
+    let expected = Opts {
+        json: false,
+        format: None,
+        user: Some("me".to_owned()),
+        password: Some("hunter2".to_owned()),
+        file: Some("in.txt".to_owned()),
+        stdin: false,
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Args conflict with config values.
+#[test]
+fn conflict_with_config() {
+    let err = try_merge(&["--json", "--stdin"], "format: text").unwrap_err();
+    assert_eq!(ErrorKind::ArgumentConflict, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("the argument '--json' cannot be used with 'config key format'"),
+        "{message}"
+    );
+}
+
+/// A `false` flag in the config doesn't count as set.
+#[test]
+fn false_flag_in_config() -> Result<()> {
+    let opts = try_merge(&["--format=text", "--stdin"], "json: false")?;
+    assert_eq!(Some("text".to_owned()), opts.format);
+    Ok(())
+}
+
+/// Requirements of config values are checked.
+#[test]
+fn missing_requirement() {
+    let err = try_merge(&["--stdin"], "user: me").unwrap_err();
+    assert_eq!(ErrorKind::MissingRequiredArgument, err.kind());
+    let message = err.to_string();
+    assert!(message.contains("--password <PASSWORD>"), "{message}");
+}
+
+/// Required groups are checked after merging.
+#[test]
+fn missing_required_group() {
+    let err = try_merge(&[], "").unwrap_err();
+    assert_eq!(ErrorKind::MissingRequiredArgument, err.kind());
+    let message = err.to_string();
+    assert!(message.contains("<--file <FILE>|--stdin>"), "{message}");
+}
+
+/// Only one arg in a group can be set across the args and the config.
+#[test]
+fn group_conflict() {
+    let err = try_merge(&["--stdin"], "file: in.txt").unwrap_err();
+    assert_eq!(ErrorKind::ArgumentConflict, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("the argument '--stdin' cannot be used with 'config key file'"),
+        "{message}"
+    );
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = try_merge(&["--user=me", "--json"], "password: hunter2\nfile: in.txt")?;
+    let config = serde_yaml::to_string(&OptsConfig::from(&opts))?;
+    assert_eq!(opts, try_merge(&[], &config)?);
+    Ok(())
+}
@@ -51,3 +51,24 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Skipped fields aren't converted back into the config.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from([
+        "myapp",
+        "--flag=from-arg",
+        "--ignore-me-flag=true",
+    ]);
+    let opts = Opts::from_merged(matches, None);
+    let config = OptsConfig::from(&opts);
+    assert_eq!("flag: from-arg\n", serde_yaml::to_string(&config)?);
+
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let expected = Opts {
+        flag: "from-arg".to_owned(),
+        ignore_me_flag: NonSerializable(false),
+    };
+    assert_eq!(expected, Opts::from_merged(matches, Some(config)));
+    Ok(())
+}
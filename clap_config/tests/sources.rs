@@ -0,0 +1,183 @@
+//! Tests that the source of each merged value is recorded.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::Source;
+use color_eyre::Result;
+use clap_config::ExplainFormat;
+use pretty_assertions::assert_eq;
+use std::sync::Once;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value = "default")]
+    name: String,
+
+    #[clap(long, env = "CLAP_CONFIG_TEST_SOURCES_TOKEN")]
+    token: Option<String>,
+
+    #[clap_config(lockable)]
+    #[clap(long)]
+    registry: Option<String>,
+
+    #[clap(long)]
+    verbose: bool,
+
+    #[clap(long)]
+    output: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct SubcommandAOptions {
+    #[clap(long, default_value = "default-a")]
+    flag_a: String,
+
+    #[clap(long)]
+    flag_b: Option<String>,
+}
+
+const SYSTEM_CONFIG: &str = "
+name: system
+registry:
+  value: https://registry.internal
+  locked: true
+subcommand_a:
+  flag_a: system-a
+";
+
+const USER_CONFIG: &str = "
+name: user
+registry: https://example.com
+";
+
+static SET_ENV: Once = Once::new();
+
+fn matches(args: &[&str]) -> Result<clap::ArgMatches> {
+    SET_ENV.call_once(|| {
+        // SAFETY: all the tests in this file set the env var through this `Once` before reading
+        // it.
+        unsafe {
+            std::env::set_var("CLAP_CONFIG_TEST_SOURCES_TOKEN", "secret");
+        }
+    });
+    Ok(<Opts as CommandFactory>::command()
+        .try_get_matches_from(std::iter::once("myapp").chain(args.iter().copied()))?)
+}
+
+fn layers() -> Result<[(String, OptsConfig); 2]> {
+    Ok([
+        (
+            "/etc/myapp.yaml".to_owned(),
+            serde_yaml::from_str(SYSTEM_CONFIG)?,
+        ),
+        (
+            "~/.myapp.yaml".to_owned(),
+            serde_yaml::from_str(USER_CONFIG)?,
+        ),
+    ])
+}
+
+fn merge(args: &[&str]) -> Result<(Opts, clap_config::Sources)> {
+    Ok(Opts::try_from_merged_with_sources(
+        matches(args)?,
+        layers()?,
+    )?)
+}
+
+/// Each value records whether it came from the command line, an env var, a config layer, or a
+/// default, including values in nested subcommands.
+#[test]
+fn record_sources() -> Result<()> {
+    let (opts, sources) = merge(&["--verbose", "subcommand-a"])?;
+    assert_eq!("user", opts.name);
+    assert_eq!(Some("https://registry.internal"), opts.registry.as_deref());
+
+    let expected = vec![
+        ("name", Source::Config("~/.myapp.yaml".to_owned())),
+        (
+            "token",
+            Source::EnvVariable("CLAP_CONFIG_TEST_SOURCES_TOKEN".to_owned()),
+        ),
+        ("registry", Source::Config("/etc/myapp.yaml".to_owned())),
+        ("verbose", Source::CommandLine),
+        ("output", Source::Unset),
+        (
+            "subcommand_a.flag_a",
+            Source::Config("/etc/myapp.yaml".to_owned()),
+        ),
+        ("subcommand_a.flag_b", Source::Unset),
+    ];
+    let actual: Vec<_> = sources
+        .iter()
+        .map(|(key, source)| (key, source.clone()))
+        .collect();
+    assert_eq!(expected, actual);
+    assert_eq!(Some(&Source::CommandLine), sources.get("verbose"));
+    assert_eq!(None, sources.get("subcommand_b"));
+    Ok(())
+}
+
+/// The text report lists the values each value shadowed.
+#[test]
+fn explain_text() -> Result<()> {
+    let report = Opts::explain_config(
+        matches(&["--name=cli", "subcommand-a"])?,
+        layers()?,
+        ExplainFormat::Text,
+    )?;
+    let expected = "\
+KEY                  VALUE                      SOURCE
+name                 cli                        command line
+  (shadowed)         user                       config ~/.myapp.yaml
+  (shadowed)         system                     config /etc/myapp.yaml
+  (shadowed)         default                    default
+token                secret                     env var CLAP_CONFIG_TEST_SOURCES_TOKEN
+registry             https://registry.internal  config /etc/myapp.yaml
+  (shadowed)         https://example.com        config ~/.myapp.yaml
+verbose              false                      default
+output                                          unset
+subcommand_a.flag_a  system-a                   config /etc/myapp.yaml
+  (shadowed)         default-a                  default
+subcommand_a.flag_b                             unset
+";
+    assert_eq!(expected, report);
+    Ok(())
+}
+
+/// The JSON report has one object per value.
+#[test]
+fn explain_json() -> Result<()> {
+    let report = Opts::explain_config(matches(&["subcommand-a"])?, layers()?, ExplainFormat::Json)?;
+    let json: serde_json::Value = serde_json::from_str(&report)?;
+    let expected = serde_json::json!({
+        "key": "name",
+        "value": ["user"],
+        "source": { "kind": "config", "name": "~/.myapp.yaml" },
+        "shadowed": [
+            { "value": ["system"], "source": { "kind": "config", "name": "/etc/myapp.yaml" } },
+            { "value": ["default"], "source": { "kind": "default" } },
+        ],
+    });
+    assert_eq!(expected, json[0]);
+    assert_eq!(7, json.as_array().map_or(0, Vec::len));
+    Ok(())
+}
+
+/// Converting the merged struct back into a config and merging that gives the same values.
+#[test]
+fn round_trip() -> Result<()> {
+    let (opts, _) = merge(&["--verbose", "subcommand-a"])?;
+    let config = OptsConfig::from(&opts);
+    let merged = Opts::try_from_merged(matches(&["subcommand-a"])?, Some(config))?;
+    assert_eq!(opts, merged);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Tests that subcommand configs can be nested under a key instead of flattened.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use clap_config::ExampleFormat;
+use clap_config::Source;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(subcommands_key)]
+pub struct Opts {
+    /// A flag with the same name as a subcommand.
+    #[clap(long)]
+    subcommand_a: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+#[clap_config(subcommands_key = "subcommands")]
+pub struct SubcommandAOptions {
+    #[clap(long, default_value = "default")]
+    flag_a: String,
+
+    #[clap(subcommand)]
+    cmd: Option<NestedSubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub enum NestedSubCommand {
+    Nested(NestedOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct NestedOptions {
+    #[clap(long)]
+    flag_nested: Option<String>,
+}
+
+const CONFIG: &str = "\
+subcommand_a: flag
+commands:
+  subcommand_a:
+    flag_a: config
+    subcommands:
+      nested:
+        flag_nested: nested
+";
+
+/// The subcommand configs are read from under the key, next to a flag with the same name.
+#[test]
+fn merge() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from([
+        "myapp",
+        "subcommand-a",
+        "nested",
+    ])?;
+    let config: OptsConfig = serde_yaml::from_str(CONFIG)?;
+    let (opts, sources) =
+        Opts::try_from_merged_with_sources(matches, [("config".to_owned(), config)])?;
+    assert_eq!(
+        Opts {
+            subcommand_a: Some("flag".to_owned()),
+            cmd: Some(SubCommand::SubcommandA(SubcommandAOptions {
+                flag_a: "config".to_owned(),
+                cmd: Some(NestedSubCommand::Nested(NestedOptions {
+                    flag_nested: Some("nested".to_owned()),
+                })),
+            })),
+        },
+        opts
+    );
+    let config = Source::Config("config".to_owned());
+    assert_eq!(Some(&config), sources.get("subcommand_a"));
+    assert_eq!(Some(&config), sources.get("commands.subcommand_a.flag_a"));
+    assert_eq!(
+        Some(&config),
+        sources.get("commands.subcommand_a.subcommands.nested.flag_nested")
+    );
+    Ok(())
+}
+
+/// Converting back into a config nests the subcommand configs under the key again.
+#[test]
+fn round_trip() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from([
+        "myapp",
+        "subcommand-a",
+        "nested",
+    ])?;
+    let opts = Opts::try_from_merged(matches, Some(serde_yaml::from_str(CONFIG)?))?;
+    assert_eq!(CONFIG, serde_yaml::to_string(&OptsConfig::from(&opts))?);
+    Ok(())
+}
+
+/// The example config and strict loading use the nested layout.
+#[test]
+fn example_and_strict() -> Result<()> {
+    let example = OptsConfig::example_config(ExampleFormat::Yaml);
+    assert!(
+        example.contains("\ncommands:\n  subcommand_a:\n"),
+        "{example}"
+    );
+    assert!(
+        example.contains("\n    subcommands:\n      nested:\n"),
+        "{example}"
+    );
+
+    let err = OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(
+        "commands:\n  subcommand_a:\n    flag_b: b\n",
+    ))
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unknown config key 'commands.subcommand_a.flag_b'"),
+        "{err}"
+    );
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Tests that strict config loading reports unknown keys with suggestions.
+
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct Opts {
+    #[clap(long)]
+    flag_string: Option<String>,
+
+    #[clap(long, hide = true)]
+    secret: Option<String>,
+
+    #[clap_config(skip)]
+    #[clap(long)]
+    skipped: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB,
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct SubcommandAOptions {
+    #[clap(long)]
+    flag_a: Option<String>,
+}
+
+fn load(config: &str) -> Result<OptsConfig, serde_yaml::Error> {
+    OptsConfig::deserialize_strict(serde_yaml::Deserializer::from_str(config))
+}
+
+/// Valid keys load as usual, including hidden args and subcommand sections.
+#[test]
+fn valid_keys() -> Result<()> {
+    let config = load("flag_string: a\nsecret: b\nsubcommand_a:\n  flag_a: c\n")?;
+    assert_eq!(Some("a"), config.flag_string.as_deref());
+    assert_eq!(Some("b"), config.secret.as_deref());
+    assert_eq!(
+        Some("c"),
+        config
+            .cmd
+            .and_then(|cmd| cmd.subcommand_a)
+            .and_then(|sub| sub.flag_a)
+            .as_deref()
+    );
+    Ok(())
+}
+
+/// Unknown keys at every level are reported with the most similar valid key.
+#[test]
+fn unknown_keys() {
+    let err = load("flag_strng: a\nskipped: b\nsubcommand_a:\n  flg_a: c\nsubcommand_c: {}\n")
+        .unwrap_err();
+    let expected = "\
+unknown config key 'flag_strng'
+
+  tip: a similar key exists: 'flag_string'
+
+unknown config key 'skipped'
+
+unknown config key 'subcommand_a.flg_a'
+
+  tip: a similar key exists: 'subcommand_a.flag_a'
+
+unknown config key 'subcommand_c'
+
+  tip: a similar key exists: 'subcommand_a'";
+    assert_eq!(expected, err.to_string());
+}
+
+/// Strict loading works with any self-describing format.
+#[test]
+fn toml() {
+    let err =
+        OptsConfig::deserialize_strict(toml::Deserializer::new("[subcommand_a]\nflag = 'a'\n"))
+            .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unknown config key 'subcommand_a.flag'\n\n  tip: a similar key exists: 'subcommand_a.flag_a'"),
+        "{err}"
+    );
+}
@@ -0,0 +1,141 @@
+//! Tests that config sections of subcommands that weren't invoked are listed, and that sections
+//! that don't match any subcommand are an error.
+
+use clap::CommandFactory;
+use clap::Parser;
+use clap_config::ClapConfig;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct Opts {
+    #[clap(long)]
+    flag: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub enum SubCommand {
+    SubcommandA(SubcommandAOptions),
+    SubcommandB(SubcommandBOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct SubcommandAOptions {
+    #[clap(long)]
+    flag_a: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Option<NestedSubCommand>,
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct SubcommandBOptions {
+    #[clap(long)]
+    flag_b: Option<String>,
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub enum NestedSubCommand {
+    Nested(NestedOptions),
+}
+
+#[derive(ClapConfig, Parser, Debug)]
+pub struct NestedOptions {
+    #[clap(long)]
+    flag_nested: Option<String>,
+}
+
+const CONFIG: &str = "
+flag: a
+flag_typo: b
+subcommand_a:
+  flag_a: c
+  nested:
+    flag_nested: d
+subcommand_b:
+  flag_b: e
+";
+
+fn unused_sections(args: &[&str]) -> Result<Vec<String>> {
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(args)?;
+    let config: OptsConfig = serde_yaml::from_str(CONFIG)?;
+    let (_, sources) =
+        Opts::try_from_merged_with_sources(matches, [("config".to_owned(), config)])?;
+    Ok(sources.unused_sections().to_vec())
+}
+
+fn merge(args: &[&str], config: &str) -> Result<Opts, clap::Error> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(args);
+    Opts::try_from_merged(matches, Some(serde_yaml::from_str(config).unwrap()))
+}
+
+/// Sections of other subcommands are unused, at every level.
+#[test]
+fn subcommand_invoked() -> Result<()> {
+    assert_eq!(
+        vec!["subcommand_b"],
+        unused_sections(&["myapp", "subcommand-a", "nested"])?
+    );
+    assert_eq!(
+        vec!["subcommand_b", "subcommand_a.nested"],
+        unused_sections(&["myapp", "subcommand-a"])?
+    );
+    Ok(())
+}
+
+/// Without a subcommand, every subcommand section is unused.
+#[test]
+fn no_subcommand() -> Result<()> {
+    assert_eq!(
+        vec!["subcommand_a", "subcommand_b"],
+        unused_sections(&["myapp"])?
+    );
+    Ok(())
+}
+
+/// A section that doesn't match any subcommand is an error, with the most similar subcommand.
+#[test]
+fn unknown_section() {
+    let err = merge(&["myapp"], "subcomand_a:\n  flag_a: c\n").unwrap_err();
+    assert_eq!(clap::error::ErrorKind::InvalidSubcommand, err.kind());
+    assert!(
+        err.to_string().contains(
+            "config section 'subcomand_a' doesn't match any subcommand\n\n  \
+             tip: a similar subcommand exists: 'subcommand_a'"
+        ),
+        "{err}"
+    );
+
+    // Other unknown keys are left to strict loading.
+    merge(&["myapp"], "flag_typo: b\n").unwrap();
+}
+
+/// Unknown sections are reported at every level, also in the sections of subcommands that weren't
+/// invoked.
+#[test]
+fn unknown_nested_section() {
+    let err = merge(&["myapp", "subcommand-b"], "subcommand_a:\n  nestd: {}\n").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("config section 'subcommand_a.nestd' doesn't match any subcommand"),
+        "{err}"
+    );
+}
+
+/// Unknown sections of any config layer are reported.
+#[test]
+fn merge_layers() -> Result<()> {
+    let lower: OptsConfig = serde_yaml::from_str("subcommand_c: {}\n")?;
+    let higher: OptsConfig = serde_yaml::from_str("subcommand_a: {}\n")?;
+    let matches = <Opts as CommandFactory>::command().try_get_matches_from(["myapp"])?;
+    let err = Opts::try_from_merged_layers(matches, [lower, higher]).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("config section 'subcommand_c' doesn't match any subcommand"),
+        "{err}"
+    );
+    Ok(())
+}
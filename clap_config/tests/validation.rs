@@ -0,0 +1,214 @@
+//! Tests that config values are validated by the same value parsers as the command line.
+
+use clap::builder::PossibleValuesParser;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use clap::Parser;
+use clap::ValueEnum;
+use clap_config::ClapConfig;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct Opts {
+    #[clap(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..100))]
+    port: u16,
+
+    #[clap(long, value_parser = PossibleValuesParser::new(["text", "json"]))]
+    format: Option<String>,
+
+    #[clap(value_enum, long, default_value_t)]
+    color: Color,
+
+    #[clap(long, value_delimiter = ',', value_parser = parse_override)]
+    r#override: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum, PartialEq)]
+pub enum Color {
+    #[default]
+    Auto,
+    NeverEver,
+}
+
+/// Parse a single `=`-separated key-value pair.
+fn parse_override(s: &str) -> Result<(String, String)> {
+    let (k, v) = s
+        .split_once('=')
+        .ok_or_else(|| eyre!("no `=` found in `{s}`"))?;
+    if k.is_empty() {
+        return Err(eyre!("empty key in `{s}`"));
+    }
+    Ok((k.to_owned(), v.to_owned()))
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct CustomOpts {
+    #[clap(long, value_parser = parse_pair)]
+    pair: Option<Pair>,
+
+    #[clap(long, value_parser = clap::value_parser!(PathBuf))]
+    dir: Option<PathBuf>,
+}
+
+/// A custom value parser type that only implements the serde traits, not `Display`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pair {
+    key: String,
+    value: String,
+}
+
+fn parse_pair(s: &str) -> Result<Pair> {
+    let (key, value) = parse_override(s)?;
+    Ok(Pair { key, value })
+}
+
+#[derive(ClapConfig, Parser, Debug, PartialEq)]
+pub struct ThemeOpts {
+    #[clap_config(parse)]
+    #[clap(long)]
+    theme: Option<Theme>,
+}
+
+/// A `ValueEnum` that clap detects without `value_enum`, and that only implements `ValueEnum`.
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+pub enum Theme {
+    AlwaysOn,
+    Never,
+}
+
+fn try_merge(config: &str) -> Result<Opts, clap::Error> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_yaml::from_str(config).unwrap();
+    Opts::try_from_merged(matches, Some(config))
+}
+
+/// Valid config values use the same spellings as the command line.
+#[test]
+fn valid_config() -> Result<()> {
+    let opts = try_merge(
+        "
+port: 42
+format: json
+color: never-ever
+override:
+  key: val
+",
+    )?;
+    let expected = Opts {
+        port: 42,
+        format: Some("json".to_owned()),
+        color: Color::NeverEver,
+        r#override: vec![("key".to_owned(), "val".to_owned())],
+    };
+    assert_eq!(expected, opts);
+    Ok(())
+}
+
+/// Non-string scalars work in formats that are stricter than YAML.
+#[test]
+fn valid_json_config() -> Result<()> {
+    let matches = <Opts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: OptsConfig = serde_json::from_str(r#"{"port": 42}"#)?;
+    let opts = Opts::try_from_merged(matches, Some(config))?;
+    assert_eq!(42, opts.port);
+    Ok(())
+}
+
+/// Out of range values are rejected.
+#[test]
+fn invalid_range() {
+    let err = try_merge("port: 100").unwrap_err();
+    assert_eq!(ErrorKind::ValueValidation, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("invalid value '100' for 'config key port'"),
+        "{message}"
+    );
+}
+
+/// Values that aren't one of the possible values are rejected.
+#[test]
+fn invalid_possible_value() {
+    let err = try_merge("format: yaml").unwrap_err();
+    assert_eq!(ErrorKind::InvalidValue, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("invalid value 'yaml' for 'config key format'"),
+        "{message}"
+    );
+}
+
+/// Value enums use the command line spelling, not the Rust variant name.
+#[test]
+fn invalid_value_enum() {
+    let err = try_merge("color: NeverEver").unwrap_err();
+    assert_eq!(ErrorKind::InvalidValue, err.kind());
+    let message = err.to_string();
+    assert!(message.contains("tip: a similar value exists: 'never-ever'"), "{message}");
+}
+
+/// Map entries are validated with the custom value parser.
+#[test]
+fn invalid_custom_parser() {
+    let err = try_merge("override: {'': val}").unwrap_err();
+    assert_eq!(ErrorKind::ValueValidation, err.kind());
+    let message = err.to_string();
+    assert!(
+        message.contains("invalid value '=val' for 'config key override': empty key in `=val`"),
+        "{message}"
+    );
+}
+
+/// Parsed values are converted back into the same spellings as the command line.
+#[test]
+fn round_trip() -> Result<()> {
+    let opts = try_merge("port: 42\nformat: json\ncolor: never-ever\noverride:\n  key: val")?;
+    let config = OptsConfig::from(&opts);
+    assert_eq!(Some("never-ever".to_owned()), config.color);
+    let config = serde_yaml::to_string(&config)?;
+    assert_eq!(opts, try_merge(&config)?);
+    Ok(())
+}
+
+/// Custom value parser types don't need to implement `Display`: values that serialize to a string
+/// are converted back, and the others are left out.
+#[test]
+fn round_trip_without_display() -> Result<()> {
+    let matches = <CustomOpts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: CustomOptsConfig = serde_yaml::from_str("pair: a=b\ndir: /tmp")?;
+    let opts = CustomOpts::try_from_merged(matches, Some(config))?;
+    assert_eq!(
+        CustomOpts {
+            pair: Some(Pair {
+                key: "a".to_owned(),
+                value: "b".to_owned(),
+            }),
+            dir: Some(PathBuf::from("/tmp")),
+        },
+        opts
+    );
+    let config = CustomOptsConfig::from(&opts);
+    assert_eq!(None, config.pair);
+    assert_eq!(Some("/tmp".to_owned()), config.dir);
+    Ok(())
+}
+
+/// With `#[clap_config(parse)]`, a `ValueEnum` without `value_enum` uses the command line spelling,
+/// also when converted back.
+#[test]
+fn parsed_value_enum() -> Result<()> {
+    let matches = <ThemeOpts as CommandFactory>::command().get_matches_from(["myapp"]);
+    let config: ThemeOptsConfig = serde_yaml::from_str("theme: always-on")?;
+    let opts = ThemeOpts::try_from_merged(matches, Some(config))?;
+    assert_eq!(Some(Theme::AlwaysOn), opts.theme);
+    assert_eq!(
+        Some("always-on".to_owned()),
+        ThemeOptsConfig::from(&opts).theme
+    );
+    Ok(())
+}
@@ -0,0 +1,2826 @@
+use heck::ToKebabCase;
+use heck::ToSnakeCase;
+// use clap::clap_derive::ClapAttr;
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+use quote::quote_spanned;
+use syn::parse_macro_input;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
+use syn::AngleBracketedGenericArguments;
+use syn::Data;
+use syn::Attribute;
+use syn::DeriveInput;
+use syn::Expr;
+use syn::Field;
+use syn::Fields;
+use syn::GenericArgument;
+use syn::Ident;
+use syn::Meta;
+use syn::PathArguments;
+use syn::PathSegment;
+use syn::Token;
+use syn::Type;
+use syn::TypePath;
+use syn::TypeTuple;
+use syn::Variant;
+
+const CLAP_CONFIG_ATTR_NAME: &str = "clap_config";
+/// The key for `#[clap_config(subcommands_key)]` without a value.
+const DEFAULT_SUBCOMMANDS_KEY: &str = "commands";
+/// The key for `#[clap_config(defaults_key)]` without a value.
+const DEFAULT_DEFAULTS_KEY: &str = "defaults";
+/// The key for `#[clap_config(aliases_key)]` without a value.
+const DEFAULT_ALIASES_KEY: &str = "aliases";
+/// The key for `#[clap_config(args_key)]` without a value.
+const DEFAULT_ARGS_KEY: &str = "args";
+/// The key for `#[clap_config(default_command_key)]` without a value.
+const DEFAULT_DEFAULT_COMMAND_KEY: &str = "default_command";
+
+/// Generate a config struct and a method to merge the two values together.
+///
+/// See the `clap_config` crate for documentation, this crate is an implementation detail.
+#[proc_macro_derive(ClapConfig, attributes(clap_config))]
+pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    // Name of the struct we're creating a Config version of.
+    let input_ident = input.ident;
+    // Name of the config struct we' creating.
+    let config_ident = &get_config_ident(&input_ident);
+
+    let container_attrs = match ContainerAttrs::parse(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let config_fields;
+    let merge_method;
+    let relax_method;
+    let config_merge_method;
+    let config_defaults_method;
+    let config_example_method;
+    let config_keys_method;
+    let config_globals_method;
+    let from_impl_body;
+    let merge_config_impl;
+    // Subcommand configs implement `Deserialize` themselves, see `enum_config_deserialize_impl`.
+    let derive_deserialize;
+    let config_deserialize_impl;
+
+    let data = &input.data;
+    match *data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(_) if container_attrs.defaults_key.is_some() => {
+                let span = container_attrs.defaults_key.as_ref().map(|(_, span)| *span).expect("checked is_some");
+                return syn::Error::new(span, "`clap_config(defaults_key)` is only supported on subcommand enums")
+                    .into_compile_error()
+                    .into();
+            }
+            Fields::Named(_) if container_attrs.default_command_key.is_some() => {
+                let span = container_attrs.default_command_key.as_ref().map(|(_, span)| *span).expect("checked is_some");
+                return syn::Error::new(span, "`clap_config(default_command_key)` is only supported on subcommand enums")
+                    .into_compile_error()
+                    .into();
+            }
+            Fields::Named(ref fields) => {
+                let input_fields = &fields.named;
+                config_fields = make_fields_optional(&container_attrs, input_fields);
+                merge_method = struct_merge_method(&container_attrs, config_ident, input_fields);
+                relax_method = struct_relax_method(&container_attrs, input_fields);
+                config_merge_method = struct_config_merge_method(&container_attrs, input_fields);
+                config_defaults_method =
+                    struct_config_defaults_method(&container_attrs, &input_ident, input_fields);
+                config_example_method =
+                    struct_config_example_method(&container_attrs, &input_ident, input_fields);
+                config_keys_method = struct_config_keys_method(&container_attrs, input_fields);
+                config_globals_method =
+                    struct_config_globals_method(&container_attrs, &input_ident, input_fields);
+                from_impl_body = struct_from_impl_body(&container_attrs, input_fields);
+                derive_deserialize = quote!(serde::Deserialize,);
+                config_deserialize_impl = quote!();
+                merge_config_impl = quote! {
+                    impl ::clap_config::__private::MergeConfig for #input_ident {
+                        type Config = #config_ident;
+
+                        fn try_from_merged(
+                            matches: clap::ArgMatches,
+                            config: ::std::option::Option<#config_ident>,
+                        ) -> ::std::result::Result<Self, clap::Error> {
+                            Self::try_from_merged(matches, config)
+                        }
+
+                        fn prepare_args(
+                            cmd: clap::Command,
+                            args: ::std::vec::Vec<::std::ffi::OsString>,
+                            config: ::std::option::Option<&#config_ident>,
+                        ) -> ::std::result::Result<(clap::Command, ::std::vec::Vec<::std::ffi::OsString>), clap::Error> {
+                            Self::__prepare_args(cmd, args, config)
+                        }
+
+                        fn relax_command(cmd: clap::Command) -> clap::Command {
+                            Self::relax_command(cmd)
+                        }
+                    }
+                };
+            }
+            _ => unimplemented!("Unimplemented struct field"),
+        },
+        Data::Enum(ref data) => {
+            if let Some(span) = container_attrs.opt_in {
+                return syn::Error::new(span, "`clap_config(opt_in)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some((_, span)) = container_attrs.merge {
+                return syn::Error::new(span, "`clap_config(merge)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some((_, span)) = container_attrs.precedence {
+                return syn::Error::new(span, "`clap_config(precedence)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some(span) = container_attrs.lockable {
+                return syn::Error::new(span, "`clap_config(lockable)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some(span) = container_attrs.nullable {
+                return syn::Error::new(span, "`clap_config(nullable)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some((_, span)) = container_attrs.subcommands_key {
+                return syn::Error::new(span, "`clap_config(subcommands_key)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some((_, span)) = container_attrs.aliases_key {
+                return syn::Error::new(span, "`clap_config(aliases_key)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            if let Some((_, span)) = container_attrs.args_key {
+                return syn::Error::new(span, "`clap_config(args_key)` is only supported on structs")
+                    .into_compile_error()
+                    .into();
+            }
+            let variants = &data.variants;
+            config_fields = variants_to_fields(&container_attrs, variants);
+            merge_method = enum_merge_method(config_ident, variants);
+            relax_method = enum_relax_method(variants);
+            config_merge_method = enum_config_merge_method(&container_attrs, variants);
+            config_defaults_method = enum_config_defaults_method(variants);
+            config_example_method = enum_config_example_method(variants);
+            config_keys_method = enum_config_keys_method(&container_attrs, variants);
+            config_globals_method = enum_config_globals_method(&container_attrs, variants);
+            from_impl_body = enum_from_impl_body(&input_ident, variants);
+            derive_deserialize = quote!();
+            config_deserialize_impl = enum_config_deserialize_impl(&container_attrs, config_ident, variants);
+            merge_config_impl = quote!();
+        }
+        _ => unimplemented!("Unimplemented input type"),
+    }
+
+    let output = quote!(
+        // We currently default everything to pub by default, so don't warn about it.
+        #[allow(private_interfaces)]
+        #[derive(
+            std::default::Default,
+            std::fmt::Debug,
+            std::clone::Clone,
+            #derive_deserialize
+            serde::Serialize,
+        )]
+        pub struct #config_ident {
+            #config_fields
+        }
+
+        impl #config_ident {
+            #config_merge_method
+
+            #config_defaults_method
+
+            #config_example_method
+
+            #config_keys_method
+
+            #config_globals_method
+        }
+
+        impl #input_ident {
+            #merge_method
+
+            #relax_method
+        }
+
+        impl ::std::convert::From<&#input_ident> for #config_ident {
+            /// Convert the merged values back into a config, e.g. to print or persist the
+            /// effective settings.
+            fn from(opts: &#input_ident) -> Self {
+                #from_impl_body
+            }
+        }
+
+        #config_deserialize_impl
+
+        #merge_config_impl
+    );
+    proc_macro::TokenStream::from(output)
+}
+
+fn variants_to_fields(
+    container_attrs: &ContainerAttrs,
+    variants: &Punctuated<syn::Variant, Comma>,
+) -> TokenStream {
+    let optional_fields = variants.iter().filter_map(|v| {
+        let name = Ident::new(
+            &v.ident.to_string().as_str().to_snake_case(),
+            v.ident.span(),
+        );
+        // Skip unit subcommand fields (as they have no opts to configure).
+        let f = get_variant_field(v)?;
+        let ty = make_subcommand_ty(&f.ty);
+        Some(quote_spanned!(f.span()=> pub #name: std::option::Option<#ty>))
+    });
+    // The shared defaults are stored as raw values, as each subcommand reads its own keys.
+    let defaults = container_attrs.defaults_key.as_ref().map(|(key, _)| quote! {
+        #[doc(hidden)]
+        #[serde(rename = #key, skip_serializing_if = "Option::is_none")]
+        pub __defaults: std::option::Option<::clap_config::__private::OtherKeys>,
+    });
+    let default_command = container_attrs.default_command_key.as_ref().map(|(key, _)| quote! {
+        /// The subcommand to run when none is given on the command line.
+        #[doc(hidden)]
+        #[serde(rename = #key, skip_serializing_if = "Option::is_none")]
+        pub __default_command: std::option::Option<std::string::String>,
+    });
+
+    quote! {
+        #(
+            #[serde(skip_serializing_if = "Option::is_none")]
+            #optional_fields,
+        )*
+        #defaults
+        #default_command
+        // The raw sections, see `enum_config_deserialize_impl`.
+        #[doc(hidden)]
+        #[serde(skip)]
+        pub __sections: ::clap_config::__private::Sections
+    }
+}
+
+/**
+Generate the `Deserialize` impl of a subcommand config, which keeps the raw sections that the config
+of each subcommand is deserialized from, so that the global args of parent commands can be read from
+the section of the invoked subcommand without the subcommand configs collecting their unknown keys.
+*/
+fn enum_config_deserialize_impl(
+    container_attrs: &ContainerAttrs,
+    config_ident: &Ident,
+    variants: &Punctuated<Variant, Comma>,
+) -> TokenStream {
+    let variant_fields = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        get_variant_field(v)?;
+        let key = name.to_string();
+        Some(quote! {
+            #name: raw.sections.section(#key).map_err(serde::de::Error::custom)?
+        })
+    });
+    let (defaults_field, defaults) = match &container_attrs.defaults_key {
+        Some((key, _)) => (
+            quote! {
+                #[serde(rename = #key)]
+                defaults: std::option::Option<::clap_config::__private::OtherKeys>,
+            },
+            quote!(__defaults: raw.defaults,),
+        ),
+        None => (quote!(), quote!()),
+    };
+    let (default_command_field, default_command) = match &container_attrs.default_command_key {
+        Some((key, _)) => (
+            quote! {
+                #[serde(rename = #key)]
+                default_command: std::option::Option<std::string::String>,
+            },
+            quote!(__default_command: raw.default_command,),
+        ),
+        None => (quote!(), quote!()),
+    };
+
+    quote! {
+        impl<'de> serde::Deserialize<'de> for #config_ident {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+                #[derive(serde::Deserialize)]
+                struct Raw {
+                    #defaults_field
+                    #default_command_field
+                    #[serde(flatten)]
+                    sections: ::clap_config::__private::Sections,
+                }
+
+                let raw = Raw::deserialize(deserializer)?;
+                Ok(Self {
+                    #(#variant_fields,)*
+                    #defaults
+                    #default_command
+                    __sections: raw.sections,
+                })
+            }
+        }
+    }
+}
+
+/**
+Get the field to use for a variant of a subcommand if there is an associated fieeld.
+
+e.g. for `SubCommand::SubCommandA(opts: Opts)` -> `Some(opts: Opts)`
+e.g. for `SubCommand::SubCommandA(Opts)` -> `Some(Opts)`
+e.g. for `SubCommand::SubCommandA` -> `None`
+*/
+fn get_variant_field(v: &Variant) -> Option<&Field> {
+    match v.fields {
+        Fields::Named(ref fields) => Some(
+            fields
+                .named
+                .iter()
+                .next()
+                .expect("Expected enum variant to have a single named field"),
+        ),
+        Fields::Unnamed(ref fields) => Some(
+            fields
+                .unnamed
+                .iter()
+                .next()
+                .expect("Expected enum variant to have a single unnamed field"),
+        ),
+        Fields::Unit => None,
+    }
+}
+
+/// Convert any fields that aren't already `Option<...>` to `Option<...>` fields, ensuring
+/// everything is optional.
+fn make_fields_optional(
+    container_attrs: &ContainerAttrs,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let mut optional_fields = vec![];
+
+    for f in fields {
+        let name = &f.ident;
+        let f_ty = &f.ty;
+
+        match is_field_configurable(container_attrs, f) {
+            Ok(true) => (),
+            Ok(false) => continue,
+            Err(e) => return e.into_compile_error(),
+        }
+
+        if is_subcommand_field(f).expect("Failed to check if subcommand field is field") {
+            let ty = make_subcommand_ty(strip_optional_wrapper_if_present(f).unwrap_or(&f.ty));
+            let serde_attr = match &container_attrs.subcommands_key {
+                Some((key, _)) => quote!(#[serde(rename = #key)]),
+                None => quote!(#[serde(flatten)]),
+            };
+            optional_fields.push(quote_spanned!(f.span()=>
+                #serde_attr
+                pub #name: std::option::Option<#ty>
+            ));
+            continue;
+        }
+
+        let (is_parsed, is_lockable, is_nullable) = match (
+            is_field_parsed(f),
+            is_field_lockable(container_attrs, f),
+            is_field_nullable(container_attrs, f),
+        ) {
+            (Ok(is_parsed), Ok(is_lockable), Ok(is_nullable)) => (is_parsed, is_lockable, is_nullable),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return e.into_compile_error(),
+        };
+
+        // The type of the value in the config, and the name of the `__private` function that
+        // deserializes it if it needs a custom one.
+        let (mut ty, raw_deserializer) = if is_parsed {
+            // The config stores the raw strings, which are run through the clap value parser when
+            // merging.
+            if strip_vec_wrapper_if_present(f).is_some() {
+                (quote!(std::vec::Vec<std::string::String>), Some("raw_values"))
+            } else {
+                (quote!(std::string::String), Some("raw_value"))
+            }
+        } else if is_vec_tuple_string(f) {
+            (
+                quote!(std::collections::BTreeMap<std::string::String, std::string::String>),
+                None,
+            )
+        } else if let Some(stripped_ty) = strip_optional_wrapper_if_present(f) {
+            (quote!(#stripped_ty), None)
+        } else {
+            (quote!(#f_ty), None)
+        };
+
+        if is_lockable {
+            ty = quote!(::clap_config::Lockable<#ty>);
+        }
+        let deserialize_with = match (raw_deserializer, is_lockable, is_nullable) {
+            (Some(raw), false, false) => Some(format!("deserialize_{raw}")),
+            (Some(raw), true, _) => Some(format!("deserialize_lockable_{raw}")),
+            (Some(raw), false, true) => Some(format!("deserialize_nullable_{raw}")),
+            (None, _, true) => Some("deserialize_nullable".to_owned()),
+            (None, _, false) => None,
+        }
+        .map(|deserialize_with| {
+            let deserialize_with = format!("::clap_config::__private::{deserialize_with}");
+            quote!(#[serde(default, deserialize_with = #deserialize_with)])
+        });
+        if is_nullable {
+            // `None` if the key is missing, `Some(None)` if it is explicitly `null`.
+            ty = quote!(std::option::Option<#ty>);
+        }
+
+        optional_fields.push(quote_spanned!(f.span()=>
+            #deserialize_with
+            pub #name: std::option::Option<#ty>
+        ));
+    }
+
+    let aliases = container_attrs.aliases_key.as_ref().map(|(key, _)| quote! {
+        #[doc(hidden)]
+        #[serde(rename = #key, skip_serializing_if = "Option::is_none")]
+        pub __aliases: std::option::Option<::clap_config::__private::Aliases>,
+    });
+    let args = container_attrs.args_key.as_ref().map(|(key, _)| quote! {
+        #[doc(hidden)]
+        #[serde(rename = #key, skip_serializing_if = "Option::is_none")]
+        pub __args: std::option::Option<std::vec::Vec<std::string::String>>,
+    });
+
+    quote! {
+        #(
+            #[serde(skip_serializing_if = "Option::is_none")]
+            #optional_fields,
+        )*
+        #aliases
+        #args
+    }
+}
+
+fn make_subcommand_ty(ty: &Type) -> Type {
+    if let Type::Path(path) = ty {
+        let ident = path
+            .path
+            .require_ident()
+            .expect("Expected subcommand type to be bare identifier.");
+        let new_ident = get_config_ident(ident);
+        Type::Path(TypePath {
+            qself: None,
+            path: syn::Path::from(PathSegment::from(new_ident)),
+        })
+    } else {
+        panic!("Expected the subcommand type to be a bare identifier type.");
+    }
+}
+
+fn get_config_ident(ident: &Ident) -> Ident {
+    format_ident!("{ident}Config")
+}
+
+/**
+Generate method that merges our config into the clap-generated struct, with precedence being (by
+default, see [`Precedence`]):
+
+- Things specified via `--arg` or `$ENV_VAR`
+- Things in the config
+- Clap defaults
+*/
+fn struct_merge_method(
+    container_attrs: &ContainerAttrs,
+    config_ident: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let struct_fields = fields.iter().map(|f| {
+        let name = &f.ident;
+        quote!(#name)
+    });
+
+    // The `(arg id, required id)` pairs from `requires` attributes, checked after merging.
+    let mut requires = vec![];
+
+    // Conditional defaults are resolved before any of the config values are taken, as they can
+    // depend on any other field.
+    let mut conditional_defaults = vec![];
+    for f in fields {
+        let name_str = f.ident.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+        let conditions = match conditional_default_exprs(f) {
+            Ok(conditions) if conditions.is_empty() => continue,
+            Ok(conditions) => conditions,
+            Err(e) => return e.into_compile_error(),
+        };
+        let ident = format_ident!("__conditional_default_{name_str}");
+        let raw_ident = format_ident!("__conditional_default_raw_{name_str}");
+        let (parse_fn, plain_default) = if strip_vec_wrapper_if_present(f).is_some() {
+            (
+                quote!(parse_config_values(&command, #name_str, #name_str, vec![raw])),
+                quote!(::clap_config::__private::parse_default_values(&command, #name_str)),
+            )
+        } else {
+            (
+                quote!(parse_config_value(&command, #name_str, #name_str, raw)),
+                quote! {
+                    ::clap_config::__private::parse_default_values(&command, #name_str)
+                        .and_then(|values| values.into_iter().next())
+                },
+            )
+        };
+        conditional_defaults.push(quote! {
+            let #raw_ident = ::clap_config::__private::conditional_default(
+                &command,
+                &matches,
+                {
+                    let mut conditions = vec![];
+                    #(#conditions)*
+                    conditions
+                },
+                &config_raw,
+            );
+            // Without a matching condition, the plain default applies.
+            let #ident = match #raw_ident.clone() {
+                Some(raw) => raw.map(|raw| ::clap_config::__private::#parse_fn).transpose()?,
+                None => #plain_default,
+            };
+        });
+    }
+
+    // Looks up the raw config value of a field, for matching conditional defaults.
+    let config_raw = (!conditional_defaults.is_empty()).then(|| {
+        let arms = fields.iter().filter_map(|f| {
+            let name = &f.ident;
+            let name_str = name.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+            let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str).to_owned();
+            if !is_field_configurable(container_attrs, f).unwrap_or_default()
+                || is_subcommand_field(f).unwrap_or_default()
+            {
+                return None;
+            }
+            // A `false` flag in the config is the same as not passing it.
+            let filter = matches!(&f.ty, Type::Path(TypePath { path, .. }) if path.is_ident("bool"))
+                .then(|| quote!(.filter(|v| **v)));
+            let value = if is_field_lockable(container_attrs, f).unwrap_or_default() {
+                Some(quote!(.map(::clap_config::Lockable::value)))
+            } else if is_field_nullable(container_attrs, f).unwrap_or_default() {
+                Some(quote!(.and_then(std::option::Option::as_ref)))
+            } else {
+                None
+            };
+            Some(quote! {
+                #name_str => config
+                    .as_ref()
+                    .and_then(|c| c.#name.as_ref())
+                    #value
+                    #filter
+                    .map(::clap_config::__private::raw_config_values),
+            })
+        });
+        quote! {
+            let config_raw = |id: &str| match id {
+                #(#arms)*
+                _ => None,
+            };
+        }
+    });
+
+    let field_updates = fields.iter().map(|f| {
+        let name = &f.ident;
+        let ty = &f.ty;
+        let span = ty.span();
+        let name_str = name.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+
+        let is_configurable = match is_field_configurable(container_attrs, f) {
+            Ok(b) => b,
+            Err(e) => return e.into_compile_error(),
+        };
+        let is_parsed = match is_field_parsed(f) {
+            Ok(b) => b,
+            Err(e) => return e.into_compile_error(),
+        };
+        let merge_strategy = match field_merge_strategy(container_attrs, f) {
+            Ok(merge) => merge,
+            Err(e) => return e.into_compile_error(),
+        };
+        let config_wins = match field_precedence(container_attrs, f) {
+            Ok(precedence) => precedence.config_wins(),
+            Err(e) => return e.into_compile_error(),
+        };
+        let is_lockable = match is_field_lockable(container_attrs, f) {
+            Ok(b) => b,
+            Err(e) => return e.into_compile_error(),
+        };
+
+        // Whether the config value wins, ignoring locks, for recording the source of the value.
+        let config_wins_or_locked = if is_lockable {
+            quote!((#config_wins || locked))
+        } else {
+            config_wins.clone()
+        };
+
+        // A locked config value wins over any clap value.
+        let (unlock, config_field, config_wins) = if is_lockable {
+            (
+                quote! {
+                    let (config_field, locked) = ::clap_config::__private::unlock(
+                        config.as_mut().and_then(|c| c.#name.take())
+                    );
+                },
+                quote!(config_field),
+                quote! {
+                    (#config_wins || sources.config_locked(
+                        &command, #name_str, #name_str, locked, config_value.is_some(), value_source
+                    )?)
+                },
+            )
+        } else if is_field_nullable(container_attrs, f).unwrap_or_default() {
+            // An explicit `null` is the same as a missing value here, it only matters when
+            // merging config layers.
+            (quote!(), quote!(config.as_mut().and_then(|c| c.#name.take()).flatten()), config_wins)
+        } else {
+            (quote!(), quote!(config.as_mut().and_then(|c| c.#name.take())), config_wins)
+        };
+
+        let config_value_expr = if !is_configurable {
+            quote!(None)
+        } else if is_parsed {
+            let parse_fn = if strip_vec_wrapper_if_present(f).is_some() {
+                quote!(parse_config_values)
+            } else {
+                quote!(parse_config_value)
+            };
+            quote! {
+                #config_field
+                    .map(|raw| ::clap_config::__private::#parse_fn(&command, #name_str, #name_str, raw))
+                    .transpose()?
+            }
+        } else if is_vec_tuple_string_validated(f).unwrap_or_default() {
+            quote! {
+                #config_field
+                    .map(|map| ::clap_config::__private::parse_config_map(&command, #name_str, #name_str, map))
+                    .transpose()?
+            }
+        } else {
+            config_field
+        };
+
+        let is_subcommand = is_subcommand_field(f).expect("Failed to check if field is subcommand.");
+        if !is_subcommand {
+            match requires_exprs(f) {
+                Ok(exprs) => requires.extend(exprs.into_iter().map(|expr| quote! {
+                    (#name_str, clap::Id::from(#expr))
+                })),
+                Err(e) => return e.into_compile_error(),
+            }
+        }
+
+        // Conditional defaults apply when the value isn't set on the command line or in the
+        // config, so they take precedence over clap's defaults.
+        let has_conditional_default = conditional_default_exprs(f).is_ok_and(|c| !c.is_empty());
+        let conditional_default_ident = format_ident!("__conditional_default_{name_str}");
+        let conditional_default_raw = if has_conditional_default {
+            let ident = format_ident!("__conditional_default_raw_{name_str}");
+            quote!(#ident.as_ref().and_then(|raw| raw.as_deref()))
+        } else {
+            quote!(None)
+        };
+        // The default clap picked can depend on a controlling arg that the config changed, so it's
+        // replaced by the one resolved from the merged values. `value_ty` is the type clap stores.
+        let conditional_default = |value_ty: &Type| has_conditional_default.then(|| {
+            let ident = &conditional_default_ident;
+            quote! {
+                let config_value = if matches.value_source(#name_str).is_some_and(|s| s != clap::parser::ValueSource::DefaultValue) {
+                    config_value
+                } else {
+                    let _ = matches.try_remove_many::<#value_ty>(#name_str);
+                    config_value.or(#ident)
+                };
+            }
+        });
+
+        // Record whether the value was set on the command line or in the config, for checking
+        // conflicts and requirements after merging. Has to come before the value is removed from
+        // the matches.
+        // Also records where the value came from.
+        let track_presence = |config_present: TokenStream| quote! {
+            if matches.value_source(#name_str).is_some_and(|s| s != clap::parser::ValueSource::DefaultValue) {
+                present.push(::clap_config::__private::PresentArg { id: #name_str, config_key: None });
+            } else if #config_present {
+                present.push(::clap_config::__private::PresentArg { id: #name_str, config_key: Some(#name_str) });
+            }
+            sources.record(
+                &command,
+                &matches,
+                #name_str,
+                #name_str,
+                config_value.is_some()
+                    && matches.value_source(#name_str).is_none_or(|value_source| #config_wins_or_locked),
+                #conditional_default_raw,
+            );
+        };
+
+        if is_subcommand {
+            let stripped_ty = strip_optional_wrapper_if_present(f);
+            let config_ty = make_subcommand_ty(stripped_ty.unwrap_or(ty));
+            // The subcommand from the command line, or else the one chosen by the config.
+            let invoked = quote! {
+                let subcommand_config: ::std::option::Option<#config_ty> = #config_value_expr;
+                let subcommand = ::clap_config::__private::take_invoked_subcommand(
+                    &command,
+                    &mut matches,
+                    subcommand_config.as_ref().and_then(|c| c.__default_command()),
+                )?;
+            };
+            // Report the config sections of the other subcommands.
+            let unused_sections = is_configurable.then(|| quote! {
+                if let Some(config) = &subcommand_config {
+                    config.__unused_sections(subcommand.as_ref().map(|(name, _)| name.as_str()), sources);
+                }
+            });
+            // Values of nested subcommand configs are recorded with their full path.
+            let (push_key, pop_key) = match &container_attrs.subcommands_key {
+                Some((key, _)) => (quote!(sources.push(#key);), quote!(sources.pop();)),
+                None => (quote!(), quote!()),
+            };
+            if let Some(stripped_ty) = stripped_ty {
+                quote_spanned! {span=>
+                    #invoked
+                    #push_key
+                    #unused_sections
+                    let #name: #ty = subcommand
+                        .map(|(subcommand_name, subcommand_matches)| #stripped_ty :: __try_from_merged(
+                            subcommand_name,
+                            subcommand_matches,
+                            subcommand_config,
+                            sources,
+                        ))
+                        .transpose()?;
+                    #pop_key
+                }
+            } else {
+                quote_spanned! {span=>
+                    #invoked
+                    #push_key
+                    #unused_sections
+                    // Clap checks the subcommand is set, unless the subcommand of this struct was
+                    // chosen by the config.
+                    let (subcommand_name, subcommand_matches) = subcommand
+                        .ok_or_else(|| ::clap_config::__private::missing_subcommand_error(&command))?;
+                    let #name: #ty = #ty :: __try_from_merged(
+                        subcommand_name,
+                        subcommand_matches,
+                        subcommand_config,
+                        sources,
+                    )?;
+                    #pop_key
+                }
+            }
+        } else if let Some(stripped_ty) = strip_optional_wrapper_if_present(f) {
+            // User-specified field's type was `Option<T>`
+            let track_option = track_presence(quote!(config_value.is_some()));
+            let conditional_default = conditional_default(stripped_ty);
+            quote_spanned! {span=>
+                let #name: #ty = {
+                    #unlock
+                    let config_value: #ty = #config_value_expr;
+                    #track_option
+                    #conditional_default
+                    if matches.contains_id(#name_str) {
+                        let value_source = matches.value_source(#name_str).expect("checked contains_id");
+                        let matches_value: #stripped_ty = matches.remove_one(#name_str).expect("checked contains_id");
+                        if #config_wins {
+                            Some(config_value.unwrap_or(matches_value))
+                        } else {
+                            Some(matches_value)
+                        }
+                    } else {
+                        config_value
+                    }
+                };
+            }
+        } else if is_vec_tuple_string(f) {
+            let merged_value = if merge_strategy == MergeStrategy::Deep {
+                quote!(::clap_config::__private::deep_merge_map(config_value.into_iter().flatten(), matches_value))
+            } else {
+                quote!(matches_value)
+            };
+            // An empty value removes the key, also when it's in the only config that sets the map.
+            let remove_empty = if merge_strategy == MergeStrategy::Deep {
+                quote!(let config_value = ::clap_config::__private::remove_empty_values(config_value);)
+            } else {
+                quote!()
+            };
+            let track_map = track_presence(quote!(config_value.as_ref().is_some_and(|m| !m.is_empty())));
+            quote_spanned! {span=>
+                let #name: #ty = {
+                    #unlock
+                    let config_value = #config_value_expr;
+                    #remove_empty
+                    #track_map
+                    if matches.contains_id(#name_str) {
+                        let value_source = matches.value_source(#name_str).expect("checked contains_id");
+                        let matches_value: #ty = matches.remove_many(#name_str).expect("checked contains_id").collect();
+                        if #config_wins {
+                            config_value
+                                .map_or(matches_value, |m| m
+                                    .into_iter()
+                                    .collect::<Vec<(std::string::String, std::string::String)>>()
+                                )
+                        } else {
+                            #merged_value
+                        }
+                    } else {
+                        config_value
+                            .map(|h|
+                                h.into_iter()
+                                 .collect::<Vec<(std::string::String, std::string::String)>>()
+                            ).unwrap_or_default()
+                    }
+                };
+            }
+        } else if let Some(inner_ty) = strip_vec_wrapper_if_present(f) {
+            // User-specified field's type was `Vec<T>`
+            let merged_value = match merge_strategy {
+                MergeStrategy::Replace | MergeStrategy::Deep => quote!(matches_value),
+                MergeStrategy::Append => quote! {
+                    config_value.into_iter().flatten().chain(matches_value).collect()
+                },
+                MergeStrategy::Prepend => quote! {
+                    matches_value.into_iter().chain(config_value.into_iter().flatten()).collect()
+                },
+            };
+            let track_vec = track_presence(quote!(config_value.as_ref().is_some_and(|v| !v.is_empty())));
+            let conditional_default = conditional_default(inner_ty);
+            quote_spanned! {span=>
+                let #name: #ty = {
+                    #unlock
+                    let config_value: std::option::Option<#ty> = #config_value_expr;
+                    #track_vec
+                    #conditional_default
+                    if matches.contains_id(#name_str) {
+                        let value_source = matches.value_source(#name_str).expect("checked contains_id");
+                        let matches_value: #ty = matches.remove_many(#name_str).expect("checked contains_id").collect();
+                        if #config_wins {
+                            config_value.unwrap_or(matches_value)
+                        } else {
+                            #merged_value
+                        }
+                    } else {
+                        config_value.unwrap_or_default()
+                    }
+                };
+            }
+        } else {
+            // A `false` flag in the config is the same as not passing it.
+            let track_plain = if matches!(ty, Type::Path(TypePath { path, .. }) if path.is_ident("bool")) {
+                track_presence(quote!(config_value == Some(true)))
+            } else {
+                track_presence(quote!(config_value.is_some()))
+            };
+            let conditional_default = conditional_default(ty);
+            quote_spanned! {span=>
+                let #name: #ty = {
+                    #unlock
+                    let config_value: std::option::Option<#ty> = #config_value_expr;
+                    #track_plain
+                    #conditional_default
+                    if matches.contains_id(#name_str) {
+                        let value_source = matches.value_source(#name_str).expect("checked contains_id");
+                        let matches_value: #ty = matches.remove_one(#name_str).expect("checked contains_id");
+                        if #config_wins {
+                            config_value.unwrap_or(matches_value)
+                        } else {
+                            matches_value
+                        }
+                    } else {
+                        config_value.ok_or_else(|| ::clap_config::__private::missing_required_error(#name_str))?
+                    }
+                };
+            }
+        }
+    }).collect::<Vec<_>>();
+
+    let expand_aliases_method = container_attrs.aliases_key.as_ref().map(|(key, _)| quote! {
+        /// Expand an alias from the config at the start of `args`, the full command line
+        /// including the binary name. Aliases that are also the name of a subcommand are
+        /// ignored.
+        pub fn expand_aliases<I, T>(
+            args: I,
+            config: ::std::option::Option<&#config_ident>,
+        ) -> ::std::result::Result<::std::vec::Vec<::std::ffi::OsString>, clap::Error>
+        where
+            I: ::std::iter::IntoIterator<Item = T>,
+            T: ::std::convert::Into<::std::ffi::OsString>,
+        {
+            let args = args.into_iter().map(::std::convert::Into::into).collect();
+            match config.and_then(|config| config.__aliases.as_ref()) {
+                Some(aliases) => aliases.expand(&::clap_config::__private::command::<Self>(), #key, args),
+                None => Ok(args),
+            }
+        }
+    });
+    let expand_aliases = container_attrs.aliases_key.is_some().then(|| quote! {
+        let args = Self::expand_aliases(args, config)?;
+    });
+
+    // Parsing an argv with aliases or args from the config needs the full clap command, so these
+    // require `clap::Parser`.
+    let parse_methods = (container_attrs.aliases_key.is_some() || container_attrs.args_key.is_some()).then(|| quote! {
+        #expand_aliases_method
+
+        /// Expand an alias and insert the extra args from the config in `args`, then parse them
+        /// and merge them with the config, exiting with a clap error if they are invalid.
+        pub fn parse_from_merged<I, T>(
+            args: I,
+            config: ::std::option::Option<#config_ident>,
+        ) -> Self
+        where
+            I: ::std::iter::IntoIterator<Item = T>,
+            T: ::std::convert::Into<::std::ffi::OsString>,
+        {
+            Self::try_parse_from_merged(args, config).unwrap_or_else(|e| e.exit())
+        }
+
+        /// Expand an alias and insert the extra args from the config in `args`, then parse them
+        /// and merge them with the config, see `parse_from_merged`.
+        pub fn try_parse_from_merged<I, T>(
+            args: I,
+            config: ::std::option::Option<#config_ident>,
+        ) -> ::std::result::Result<Self, clap::Error>
+        where
+            I: ::std::iter::IntoIterator<Item = T>,
+            T: ::std::convert::Into<::std::ffi::OsString>,
+        {
+            let args = args.into_iter().map(::std::convert::Into::into).collect();
+            let command = Self::relax_command(<Self as clap::CommandFactory>::command());
+            let (command, args) = Self::__prepare_args(command, args, config.as_ref())?;
+            let matches = command.try_get_matches_from(args)?;
+            Self::try_from_merged(matches, config)
+        }
+    });
+
+    quote! {
+        #parse_methods
+
+        /// Expand an alias and insert the extra args from the config in `args`, see
+        /// `clap_config::__private::MergeConfig::prepare_args`.
+        #[doc(hidden)]
+        pub fn __prepare_args(
+            cmd: clap::Command,
+            args: ::std::vec::Vec<::std::ffi::OsString>,
+            config: ::std::option::Option<&#config_ident>,
+        ) -> ::std::result::Result<(clap::Command, ::std::vec::Vec<::std::ffi::OsString>), clap::Error> {
+            #expand_aliases
+            let config_args = config.map(#config_ident::__config_args).unwrap_or_default();
+            if config_args.is_empty() {
+                return Ok((cmd, args));
+            }
+            let args = config_args.insert(&::clap_config::__private::command::<Self>(), &args);
+            Ok((config_args.override_command(cmd), args))
+        }
+
+        /// Merge the values from the command line and the config, exiting with a clap error if
+        /// they are invalid.
+        pub fn from_merged(
+            matches: clap::ArgMatches,
+            config: ::std::option::Option<#config_ident>
+        ) -> Self {
+            Self::try_from_merged(matches, config).unwrap_or_else(|e| e.exit())
+        }
+
+        /// Merge the values from the command line and the config layers, exiting with a clap
+        /// error if they are invalid.
+        ///
+        /// Later layers take precedence over earlier ones, e.g. pass the system config, then the
+        /// user config, then the project config.
+        pub fn from_merged_layers(
+            matches: clap::ArgMatches,
+            layers: impl ::std::iter::IntoIterator<Item = #config_ident>,
+        ) -> Self {
+            Self::try_from_merged_layers(matches, layers).unwrap_or_else(|e| e.exit())
+        }
+
+        /// Merge the values from the command line and the config layers, see
+        /// `from_merged_layers`.
+        pub fn try_from_merged_layers(
+            matches: clap::ArgMatches,
+            layers: impl ::std::iter::IntoIterator<Item = #config_ident>,
+        ) -> ::std::result::Result<Self, clap::Error> {
+            // Global args and shared defaults are moved into place in each layer before merging,
+            // so a higher layer still wins.
+            let layers: ::std::vec::Vec<_> = layers.into_iter().collect();
+            let merged = layers.iter().cloned().reduce(#config_ident::merge);
+            let mut config = None;
+            for mut layer in layers {
+                layer.__prepare_layer(&matches, merged.as_ref())?;
+                config = ::clap_config::__private::merge_nested(config, Some(layer), #config_ident::merge);
+            }
+            if let Some(config) = &config {
+                config.__check_sections("")?;
+            }
+            Self::__try_from_merged(None, matches, config, &mut ::std::default::Default::default())
+        }
+
+        /// Merge the values from the command line and the named config layers, and record where
+        /// each value came from, exiting with a clap error if they are invalid.
+        ///
+        /// Later layers take precedence over earlier ones, and the names (e.g. the path of each
+        /// config file) are used in the sources.
+        pub fn from_merged_with_sources(
+            matches: clap::ArgMatches,
+            layers: impl ::std::iter::IntoIterator<Item = (::std::string::String, #config_ident)>,
+        ) -> (Self, ::clap_config::Sources) {
+            Self::try_from_merged_with_sources(matches, layers).unwrap_or_else(|e| e.exit())
+        }
+
+        /// Merge the values from the command line and the named config layers, and record where
+        /// each value came from, see `from_merged_with_sources`.
+        pub fn try_from_merged_with_sources(
+            matches: clap::ArgMatches,
+            layers: impl ::std::iter::IntoIterator<Item = (::std::string::String, #config_ident)>,
+        ) -> ::std::result::Result<(Self, ::clap_config::Sources), clap::Error> {
+            let layers: ::std::vec::Vec<_> = layers.into_iter().collect();
+            let merged = layers.iter().map(|(_, layer)| layer.clone()).reduce(#config_ident::merge);
+            let mut values = vec![];
+            let mut config = None;
+            for (name, mut layer) in layers {
+                layer.__prepare_layer(&matches, merged.as_ref())?;
+                let mut layer_values = vec![];
+                layer.__config_values("", &mut layer_values);
+                values.push((name, layer_values));
+                config = ::clap_config::__private::merge_nested(config, Some(layer), #config_ident::merge);
+            }
+            if let Some(config) = &config {
+                config.__check_sections("")?;
+            }
+            let mut sources = ::clap_config::__private::SourceRecorder::new(values);
+            let merged = Self::__try_from_merged(None, matches, config, &mut sources)?;
+            Ok((merged, sources.finish()))
+        }
+
+        /// Render a report of each merged value, where it came from, and the lower precedence
+        /// values it shadowed, e.g. for a hidden `--explain-config` flag. See
+        /// `clap_config::Sources::render`.
+        pub fn explain_config(
+            matches: clap::ArgMatches,
+            layers: impl ::std::iter::IntoIterator<Item = (::std::string::String, #config_ident)>,
+            format: ::clap_config::ExplainFormat,
+        ) -> ::std::result::Result<::std::string::String, clap::Error> {
+            let (_, sources) = Self::try_from_merged_with_sources(matches, layers)?;
+            Ok(sources.render(format))
+        }
+
+        /// Merge the values from the command line and the config.
+        pub fn try_from_merged(
+            matches: clap::ArgMatches,
+            mut config: ::std::option::Option<#config_ident>
+        ) -> ::std::result::Result<Self, clap::Error> {
+            let merged = config.clone();
+            if let Some(config) = config.as_mut() {
+                config.__prepare_layer(&matches, merged.as_ref())?;
+                config.__check_sections("")?;
+            }
+            Self::__try_from_merged(None, matches, config, &mut ::std::default::Default::default())
+        }
+
+        /// Merge the values, where `name` is the name of the subcommand this struct holds the args
+        /// of, if any.
+        #[doc(hidden)]
+        pub fn __try_from_merged(
+            name: ::std::option::Option<&str>,
+            mut matches: clap::ArgMatches,
+            mut config: ::std::option::Option<#config_ident>,
+            sources: &mut ::clap_config::__private::SourceRecorder,
+        ) -> ::std::result::Result<Self, clap::Error> {
+            let command = ::clap_config::__private::subcommand_command::<Self>(name);
+            #[allow(unused_mut)]
+            let mut present: ::std::vec::Vec<::clap_config::__private::PresentArg> = vec![];
+
+            #config_raw
+            #(#conditional_defaults)*
+
+            #(#field_updates)*
+
+            ::clap_config::__private::validate_relationships(&command, &present, &[#(#requires),*])?;
+
+            Ok(Self {
+                #(#struct_fields),*
+            })
+        }
+    }
+}
+
+/**
+Generate a method that merges a config with a higher precedence config layer, e.g. the user config
+over the system config.
+
+Fields use the same merge strategy as when merging the config with the command line, nested
+subcommand configs are merged recursively, and locked values can't be overridden.
+*/
+fn struct_config_merge_method(
+    container_attrs: &ContainerAttrs,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let field_merges = fields.iter().filter_map(|f| {
+        let name = &f.ident;
+        match is_field_configurable(container_attrs, f) {
+            Ok(true) => (),
+            Ok(false) => return None,
+            Err(e) => return Some(e.into_compile_error()),
+        }
+
+        if is_subcommand_field(f).expect("Failed to check if field is subcommand.") {
+            let ty = make_subcommand_ty(strip_optional_wrapper_if_present(f).unwrap_or(&f.ty));
+            return Some(quote! {
+                #name: ::clap_config::__private::merge_nested(self.#name, higher.#name, #ty::merge)
+            });
+        }
+
+        let merge_fn = match field_merge_strategy(container_attrs, f) {
+            Ok(MergeStrategy::Replace) => quote!(merge_replace),
+            Ok(MergeStrategy::Append) => quote!(merge_append),
+            Ok(MergeStrategy::Prepend) => quote!(merge_prepend),
+            Ok(MergeStrategy::Deep) => quote!(merge_deep),
+            Err(e) => return Some(e.into_compile_error()),
+        };
+        if is_field_nullable(container_attrs, f).unwrap_or_default() {
+            return Some(quote! {
+                #name: ::clap_config::__private::merge_nullable(
+                    self.#name,
+                    higher.#name,
+                    ::clap_config::__private::#merge_fn,
+                )
+            });
+        }
+        Some(match is_field_lockable(container_attrs, f) {
+            Ok(true) => quote! {
+                #name: ::clap_config::__private::merge_lockable(
+                    self.#name,
+                    higher.#name,
+                    ::clap_config::__private::#merge_fn,
+                )
+            },
+            Ok(false) => quote! {
+                #name: ::clap_config::__private::#merge_fn(self.#name, higher.#name)
+            },
+            Err(e) => e.into_compile_error(),
+        })
+    });
+
+    let field_keys = fields.iter().filter_map(|f| {
+        let name = f.ident.as_ref()?;
+        if !is_field_configurable(container_attrs, f).unwrap_or_default() {
+            return None;
+        }
+        if is_subcommand_field(f).unwrap_or_default() {
+            let prefix = match &container_attrs.subcommands_key {
+                Some((key, _)) => quote!(&::clap_config::__private::config_path(prefix, #key)),
+                None => quote!(prefix),
+            };
+            return Some(quote! {
+                if let Some(config) = &self.#name {
+                    config.__config_values(#prefix, values);
+                }
+            });
+        }
+        let key = name.to_string();
+        let key = key.strip_prefix("r#").unwrap_or(&key);
+        let (locked, raw) = if is_field_lockable(container_attrs, f).unwrap_or_default() {
+            (quote!(value.is_locked()), quote!(value.value()))
+        } else {
+            (quote!(false), quote!(value))
+        };
+        Some(quote! {
+            if let Some(value) = &self.#name {
+                values.push(::clap_config::__private::LayerValue {
+                    key: ::clap_config::__private::config_path(prefix, #key),
+                    locked: #locked,
+                    raw: ::clap_config::__private::raw_config_values(#raw),
+                });
+            }
+        })
+    });
+
+    let aliases_merge = container_attrs.aliases_key.is_some().then(|| quote! {
+        __aliases: ::clap_config::__private::merge_nested(
+            self.__aliases,
+            higher.__aliases,
+            ::clap_config::__private::Aliases::merge,
+        ),
+    });
+    // The args of each layer are added after the args of the layers below it, so they win.
+    let args_merge = container_attrs.args_key.is_some().then(|| quote! {
+        __args: ::clap_config::__private::merge_append(self.__args, higher.__args),
+    });
+
+    quote! {
+        /// Merge this config with a higher precedence config layer, e.g. a user config over the
+        /// system config.
+        #[allow(unused_variables)]
+        pub fn merge(self, higher: Self) -> Self {
+            Self {
+                #(#field_merges,)*
+                #aliases_merge
+                #args_merge
+            }
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_variables, clippy::ptr_arg)]
+        pub fn __config_values(
+            &self,
+            prefix: &str,
+            values: &mut ::std::vec::Vec<::clap_config::__private::LayerValue>,
+        ) {
+            #(#field_keys)*
+        }
+    }
+}
+
+/// Generate a method that merges the subcommand configs of two config layers, see
+/// [`struct_config_merge_method`].
+fn enum_config_merge_method(
+    container_attrs: &ContainerAttrs,
+    variants: &Punctuated<Variant, Comma>,
+) -> TokenStream {
+    let variant_merges = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        let ty = make_subcommand_ty(&get_variant_field(v)?.ty);
+        Some(quote! {
+            #name: ::clap_config::__private::merge_nested(self.#name, higher.#name, #ty::merge)
+        })
+    });
+
+    let variant_keys = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        get_variant_field(v)?;
+        let key = name.to_string();
+        Some(quote! {
+            if let Some(config) = &self.#name {
+                config.__config_values(&::clap_config::__private::config_path(prefix, #key), values);
+            }
+        })
+    });
+
+    let defaults_merge = container_attrs.defaults_key.is_some().then(|| quote! {
+        __defaults: ::clap_config::__private::merge_nested(
+            self.__defaults,
+            higher.__defaults,
+            ::clap_config::__private::OtherKeys::merge,
+        ),
+    });
+    let default_command_merge = container_attrs.default_command_key.is_some().then(|| quote! {
+        __default_command: ::clap_config::__private::merge_replace(
+            self.__default_command,
+            higher.__default_command,
+        ),
+    });
+
+    quote! {
+        /// Merge this config with a higher precedence config layer, e.g. a user config over the
+        /// system config.
+        #[allow(unused_variables)]
+        pub fn merge(self, higher: Self) -> Self {
+            Self {
+                #(#variant_merges,)*
+                #defaults_merge
+                #default_command_merge
+                __sections: self.__sections.merge(higher.__sections),
+            }
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_variables, clippy::ptr_arg)]
+        pub fn __config_values(
+            &self,
+            prefix: &str,
+            values: &mut ::std::vec::Vec<::clap_config::__private::LayerValue>,
+        ) {
+            #(#variant_keys)*
+        }
+    }
+}
+
+/**
+Generate a method that builds a config with every field set to its clap default value, parsed into
+the config field type, and every subcommand config filled in.
+
+Fields without a default are left unset.
+*/
+fn struct_config_defaults_method(
+    container_attrs: &ContainerAttrs,
+    input_ident: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let field_defaults = fields.iter().filter_map(|f| {
+        let name = &f.ident;
+        match is_field_configurable(container_attrs, f) {
+            Ok(true) => (),
+            Ok(false) => return None,
+            Err(e) => return Some(e.into_compile_error()),
+        }
+
+        let stripped_ty = strip_optional_wrapper_if_present(f);
+        if is_subcommand_field(f).expect("Failed to check if field is subcommand.") {
+            let ty = make_subcommand_ty(stripped_ty.unwrap_or(&f.ty));
+            return Some(quote!(#name: Some(#ty::from_clap_defaults())));
+        }
+
+        let name_str = name.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+        let vec_ty = strip_vec_wrapper_if_present(f);
+        let value = match is_field_parsed(f) {
+            // The config stores the raw strings.
+            Ok(true) if vec_ty.is_some() => {
+                quote!(::clap_config::__private::default_values(&command, #name_str))
+            }
+            Ok(true) => quote! {
+                ::clap_config::__private::default_values(&command, #name_str)
+                    .and_then(|values| values.into_iter().next())
+            },
+            Ok(false) if is_vec_tuple_string(f) => quote! {
+                ::clap_config::__private::parse_default_values::<(
+                    ::std::string::String,
+                    ::std::string::String,
+                )>(&command, #name_str)
+                .map(|values| values.into_iter().collect())
+            },
+            Ok(false) => match vec_ty {
+                Some(ty) => quote! {
+                    ::clap_config::__private::parse_default_values::<#ty>(&command, #name_str)
+                },
+                None => {
+                    let ty = stripped_ty.unwrap_or(&f.ty);
+                    quote! {
+                        ::clap_config::__private::parse_default_values::<#ty>(&command, #name_str)
+                            .and_then(|values| values.into_iter().next())
+                    }
+                }
+            },
+            Err(e) => return Some(e.into_compile_error()),
+        };
+        let wrap = if is_field_lockable(container_attrs, f).unwrap_or_default() {
+            quote!(.map(::clap_config::Lockable::Value))
+        } else if is_field_nullable(container_attrs, f).unwrap_or_default() {
+            quote!(.map(Some))
+        } else {
+            quote!()
+        };
+        Some(quote!(#name: #value #wrap))
+    });
+
+    let aliases = container_attrs.aliases_key.is_some().then(|| quote!(__aliases: None,));
+    let args = container_attrs.args_key.is_some().then(|| quote!(__args: None,));
+
+    quote! {
+        /// Build a config with every value set to its clap default, e.g. to write an initial
+        /// config file.
+        #[allow(unused_variables)]
+        pub fn from_clap_defaults() -> Self {
+            let command = ::clap_config::__private::command::<#input_ident>();
+            Self {
+                #(#field_defaults,)*
+                #aliases
+                #args
+                }
+        }
+    }
+}
+
+/// Generate a method that builds a subcommand config with the config of every subcommand set to
+/// its clap defaults, see [`struct_config_defaults_method`].
+fn enum_config_defaults_method(variants: &Punctuated<Variant, Comma>) -> TokenStream {
+    let variant_defaults = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        let ty = make_subcommand_ty(&get_variant_field(v)?.ty);
+        Some(quote!(#name: Some(#ty::from_clap_defaults())))
+    });
+
+    quote! {
+        /// Build a config with every value of every subcommand set to its clap default, e.g. to
+        /// write an initial config file.
+        pub fn from_clap_defaults() -> Self {
+            Self {
+                #(#variant_defaults,)*
+                ..Self::default()
+            }
+        }
+    }
+}
+
+/**
+Generate a method that renders an example config, with every key documented with the help text,
+default, possible values, flag, and env var of its arg, and a nested section for each subcommand.
+*/
+fn struct_config_example_method(
+    container_attrs: &ContainerAttrs,
+    input_ident: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let field_keys = fields.iter().filter_map(|f| {
+        if !is_field_configurable(container_attrs, f).unwrap_or_default() {
+            return None;
+        }
+        if is_subcommand_field(f).unwrap_or_default() {
+            let ty = make_subcommand_ty(strip_optional_wrapper_if_present(f).unwrap_or(&f.ty));
+            return Some(match &container_attrs.subcommands_key {
+                Some((key, _)) => quote!(section.section(#key, #ty::__example_section());),
+                None => quote!(section.flatten(#ty::__example_section());),
+            });
+        }
+        let name_str = f.ident.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+        // Values deserialized with serde are documented in their serde spelling, e.g. for a
+        // `ValueEnum` without `value_enum`.
+        if is_field_parsed(f).unwrap_or_default() || is_vec_tuple_string(f) {
+            return Some(quote!(section.key(&command, #name_str, #name_str);));
+        }
+        let ty = strip_vec_wrapper_if_present(f)
+            .or_else(|| strip_optional_wrapper_if_present(f))
+            .unwrap_or(&f.ty);
+        Some(quote! {
+            section.key_with_spelling(&command, #name_str, #name_str, |raw| {
+                ::clap_config::__private::config_spelling::<#ty>(&command, #name_str, raw)
+            });
+        })
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __example_section() -> ::clap_config::__private::ExampleSection {
+            let command = ::clap_config::__private::command::<#input_ident>();
+            let mut section = ::clap_config::__private::ExampleSection::default();
+            #(#field_keys)*
+            section
+        }
+
+        /// Render an example config with every key commented out, documented with the help
+        /// text, default, possible values, flag, and env var of its arg.
+        pub fn example_config(format: ::clap_config::ExampleFormat) -> ::std::string::String {
+            ::clap_config::__private::example_config(
+                &Self::__example_section(),
+                &Self::from_clap_defaults(),
+                format,
+            )
+        }
+    }
+}
+
+/// Generate a method that builds the example config sections of each subcommand, see
+/// [`struct_config_example_method`].
+fn enum_config_example_method(variants: &Punctuated<Variant, Comma>) -> TokenStream {
+    let variant_sections = variants.iter().filter_map(|v| {
+        let name = v.ident.to_string().as_str().to_snake_case();
+        let ty = make_subcommand_ty(&get_variant_field(v)?.ty);
+        Some(quote!(section.section(#name, #ty::__example_section());))
+    });
+
+    quote! {
+        #[doc(hidden)]
+        pub fn __example_section() -> ::clap_config::__private::ExampleSection {
+            let mut section = ::clap_config::__private::ExampleSection::default();
+            #(#variant_sections)*
+            section
+        }
+    }
+}
+
+/**
+Generate a method that lists the valid config keys, and a method that deserializes a config,
+failing on unknown keys with a suggestion of the most similar valid key.
+*/
+fn struct_config_keys_method(
+    container_attrs: &ContainerAttrs,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let field_keys = fields.iter().filter_map(|f| {
+        if !is_field_configurable(container_attrs, f).unwrap_or_default() {
+            return None;
+        }
+        if is_subcommand_field(f).unwrap_or_default() {
+            let ty = make_subcommand_ty(strip_optional_wrapper_if_present(f).unwrap_or(&f.ty));
+            return Some(match &container_attrs.subcommands_key {
+                Some((key, _)) => quote!(keys.section(#key, #ty::__config_keys());),
+                None => quote!(keys.flatten(#ty::__config_keys());),
+            });
+        }
+        let name_str = f.ident.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+        Some(if is_field_global(f).unwrap_or_default() {
+            quote!(keys.key(#name_str); keys.global(#name_str);)
+        } else {
+            quote!(keys.key(#name_str);)
+        })
+    });
+    let inherit_globals = container_attrs.opt_in.is_none().then(|| quote!(keys.inherit_globals();));
+    let own_args = container_attrs.args_key.as_ref().map(|_| quote! {
+        if let Some(own_args) = &self.__args {
+            args.args(own_args);
+        }
+    });
+    let subcommand_args = fields
+        .iter()
+        .filter(|f| {
+            is_field_configurable(container_attrs, f).unwrap_or_default()
+                && is_subcommand_field(f).unwrap_or_default()
+        })
+        .map(|f| {
+            let name = &f.ident;
+            quote! {
+                if let Some(config) = &self.#name {
+                    args.flatten(config.__config_args());
+                }
+            }
+        });
+    let subcommand_checks = fields
+        .iter()
+        .filter(|f| {
+            is_field_configurable(container_attrs, f).unwrap_or_default()
+                && is_subcommand_field(f).unwrap_or_default()
+        })
+        .map(|f| {
+            let name = &f.ident;
+            let prefix = match &container_attrs.subcommands_key {
+                Some((key, _)) => quote!(&::clap_config::__private::config_path(prefix, #key)),
+                None => quote!(prefix),
+            };
+            quote! {
+                if let Some(config) = &self.#name {
+                    config.__check_sections(#prefix)?;
+                }
+            }
+        });
+    let aliases_key = container_attrs.aliases_key.as_ref().map(|(key, _)| quote!(keys.key(#key);));
+    let args_key = container_attrs.args_key.as_ref().map(|(key, _)| quote!(keys.key(#key);));
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_mut)]
+        pub fn __config_keys() -> ::clap_config::__private::ConfigKeys {
+            let mut keys = ::clap_config::__private::ConfigKeys::default();
+            #inherit_globals
+            #(#field_keys)*
+            #aliases_key
+            #args_key
+            keys
+        }
+
+        /// The extra command line args of this config and of its subcommand sections.
+        #[doc(hidden)]
+        #[allow(unused_mut)]
+        pub fn __config_args(&self) -> ::clap_config::__private::ConfigArgs {
+            let mut args = ::clap_config::__private::ConfigArgs::default();
+            #own_args
+            #(#subcommand_args)*
+            args
+        }
+
+        /// Fail for the sections of this config and of its subcommand sections that don't match
+        /// any subcommand, where `prefix` is the config path of this config.
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __check_sections(&self, prefix: &str) -> ::std::result::Result<(), clap::Error> {
+            #(#subcommand_checks)*
+            Ok(())
+        }
+
+        /// Deserialize a config, failing on unknown keys (including in subcommand sections)
+        /// with a suggestion of the most similar valid key, e.g. with
+        /// `serde_yaml::Deserializer::from_str(...)`.
+        pub fn deserialize_strict<'de, D: serde::Deserializer<'de>>(
+            deserializer: D,
+        ) -> ::std::result::Result<Self, D::Error> {
+            ::clap_config::__private::deserialize_strict(deserializer, &Self::__config_keys())
+        }
+    }
+}
+
+/// Generate a method that lists the valid config keys of each subcommand, see
+/// [`struct_config_keys_method`].
+fn enum_config_keys_method(
+    container_attrs: &ContainerAttrs,
+    variants: &Punctuated<Variant, Comma>,
+) -> TokenStream {
+    let variant_keys = variants.iter().filter_map(|v| {
+        let name = v.ident.to_string().as_str().to_snake_case();
+        let ty = make_subcommand_ty(&get_variant_field(v)?.ty);
+        Some(quote!(keys.section(#name, #ty::__config_keys());))
+    });
+
+    let defaults_keys = container_attrs.defaults_key.as_ref().map(|(key, _)| quote!(keys.defaults(#key);));
+    let default_command_key = container_attrs.default_command_key.as_ref().map(|(key, _)| quote!(keys.key(#key);));
+    let default_command = match &container_attrs.default_command_key {
+        Some((key, _)) => quote!(self.__default_command.as_deref().map(|name| (#key, name))),
+        None => quote!(None),
+    };
+
+    let known_sections = variants.iter().filter_map(|v| {
+        get_variant_field(v)?;
+        Some(v.ident.to_string().as_str().to_snake_case())
+    });
+    let variant_checks = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        get_variant_field(v)?;
+        let key = name.to_string();
+        Some(quote! {
+            if let Some(config) = &self.#name {
+                config.__check_sections(&::clap_config::__private::config_path(prefix, #key))?;
+            }
+        })
+    });
+    let variant_unused = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        let kebab_case_name = v.ident.to_string().as_str().to_kebab_case();
+        get_variant_field(v)?;
+        let key = name.to_string();
+        Some(quote! {
+            if self.#name.is_some() && subcommand_name != Some(#kebab_case_name) {
+                sources.unused_section(#key);
+            }
+        })
+    });
+    let variant_args = variants.iter().filter_map(|v| {
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        let kebab_case_name = v.ident.to_string().as_str().to_kebab_case();
+        get_variant_field(v)?;
+        Some(quote! {
+            if let Some(config) = &self.#name {
+                args.section(#kebab_case_name, config.__config_args());
+            }
+        })
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_mut)]
+        pub fn __config_keys() -> ::clap_config::__private::ConfigKeys {
+            let mut keys = ::clap_config::__private::ConfigKeys::default();
+            #default_command_key
+            #(#variant_keys)*
+            #defaults_keys
+            keys
+        }
+
+        /// The config key and name of the subcommand to run when none is given on the command
+        /// line, if the config chooses one.
+        #[doc(hidden)]
+        pub fn __default_command(&self) -> ::std::option::Option<(&'static str, &str)> {
+            #default_command
+        }
+
+        /// The extra command line args of each subcommand section, by subcommand name.
+        #[doc(hidden)]
+        #[allow(unused_mut)]
+        pub fn __config_args(&self) -> ::clap_config::__private::ConfigArgs {
+            let mut args = ::clap_config::__private::ConfigArgs::default();
+            #(#variant_args)*
+            args
+        }
+
+        /// Record the subcommand sections that aren't used when running the subcommand
+        /// `subcommand_name`.
+        #[doc(hidden)]
+        pub fn __unused_sections(
+            &self,
+            subcommand_name: ::std::option::Option<&str>,
+            sources: &mut ::clap_config::__private::SourceRecorder,
+        ) {
+            #(#variant_unused)*
+        }
+
+        /// Fail for the sections that don't match any subcommand, in this config and in the
+        /// configs of its subcommands, where `prefix` is the config path of this config.
+        #[doc(hidden)]
+        pub fn __check_sections(&self, prefix: &str) -> ::std::result::Result<(), clap::Error> {
+            let known = [#(#known_sections),*];
+            ::clap_config::__private::check_unknown_sections(prefix, self.__sections.unknown(&known), &known)?;
+            #(#variant_checks)*
+            Ok(())
+        }
+    }
+}
+
+/**
+Generate methods that move the values of global args set in the section of the invoked subcommand
+into the config of the struct that defines them, before merging.
+
+Clap propagates global args to every subcommand, so they can be set in any subcommand section on
+the path to the invoked subcommand, and the most nested section wins.
+*/
+fn struct_config_globals_method(
+    container_attrs: &ContainerAttrs,
+    input_ident: &Ident,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let subcommand_field = fields.iter().find(|f| {
+        is_field_configurable(container_attrs, f).unwrap_or_default()
+            && is_subcommand_field(f).unwrap_or_default()
+    });
+    // `opt_in` structs only read the fields they include.
+    let take_own = if container_attrs.opt_in.is_none() {
+        quote!(section.and_then(|section| section.as_object_mut()?.remove(key)))
+    } else {
+        quote!(None)
+    };
+
+    let (take_subcommand, prepare_subcommand) = match subcommand_field {
+        Some(subcommand_field) => {
+            let subcommand_name = &subcommand_field.ident;
+            let hoist_fields = fields
+                .iter()
+                .filter(|f| {
+                    is_field_configurable(container_attrs, f).unwrap_or_default()
+                        && is_field_global(f).unwrap_or_default()
+                })
+                .map(|f| {
+                    let name = &f.ident;
+                    let name_str = name.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+                    let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+                    quote! {
+                        if let Some(value) = config.__take_global(&subcommand_name, &matches, #name_str, merged)? {
+                            self.#name = ::clap_config::__private::global_config::<Self>(#name_str, value)?.#name;
+                        }
+                    }
+                });
+            // The subcommand from the command line, or else the one chosen by the merged config.
+            let invoked = quote! {
+                let command = ::clap_config::__private::command::<#input_ident>();
+                let merged = merged.and_then(|merged| merged.#subcommand_name.as_ref());
+                let invoked = ::clap_config::__private::invoked_subcommand(
+                    &command,
+                    matches,
+                    merged.and_then(|merged| merged.__default_command()),
+                )?;
+            };
+            (
+                quote! {
+                    if let Some(config) = self.#subcommand_name.as_mut() {
+                        #invoked
+                        if let Some((subcommand_name, matches)) = invoked {
+                            if let Some(value) = config.__take_global(&subcommand_name, &matches, key, merged)? {
+                                return Ok(Some(value));
+                            }
+                        }
+                    }
+                },
+                quote! {
+                    if let Some(config) = self.#subcommand_name.as_mut() {
+                        #invoked
+                        if let Some((subcommand_name, matches)) = invoked {
+                            // Nested structs first, so their global args are taken from the
+                            // sections below them.
+                            config.__prepare_layer(&subcommand_name, &matches, merged)?;
+                            #(#hoist_fields)*
+                        }
+                    }
+                },
+            )
+        }
+        None => (quote!(), quote!()),
+    };
+
+    quote! {
+        /// Remove the value of the global arg with config key `key` from the section of the
+        /// invoked subcommand, or from `section`, the raw section of this config.
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __take_global(
+            &mut self,
+            matches: &clap::ArgMatches,
+            key: &str,
+            merged: ::std::option::Option<&Self>,
+            section: ::std::option::Option<&mut ::clap_config::__private::Value>,
+        ) -> ::std::result::Result<::std::option::Option<::clap_config::__private::Value>, clap::Error> {
+            #take_subcommand
+            Ok(#take_own)
+        }
+
+        /// Move the values of global args set in the sections of the invoked subcommands into
+        /// the configs of the structs that define them, and apply the shared defaults of the
+        /// invoked subcommands, before merging a config layer.
+        ///
+        /// `merged` is the merged config of all the layers, which chooses the subcommands that
+        /// run by default.
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __prepare_layer(
+            &mut self,
+            matches: &clap::ArgMatches,
+            merged: ::std::option::Option<&Self>,
+        ) -> ::std::result::Result<(), clap::Error> {
+            #prepare_subcommand
+            Ok(())
+        }
+    }
+}
+
+/**
+Generate methods that find the values of global args in the section of the invoked subcommand, see
+[`struct_config_globals_method`], and that apply the shared defaults section to the section of the
+invoked subcommand, with the values in its own section taking precedence.
+*/
+fn enum_config_globals_method(
+    container_attrs: &ContainerAttrs,
+    variants: &Punctuated<Variant, Comma>,
+) -> TokenStream {
+    let variants: Vec<_> = variants
+        .iter()
+        .filter_map(|v| {
+            let kebab_case_name = v.ident.to_string().as_str().to_kebab_case();
+            let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+            let ty = make_subcommand_ty(&get_variant_field(v)?.ty);
+            Some((kebab_case_name, name, ty))
+        })
+        .collect();
+    let take_arms = variants.iter().map(|(kebab_case_name, name, _)| {
+        let section_key = name.to_string();
+        quote! {
+            #kebab_case_name => match self.#name.as_mut() {
+                Some(config) => config.__take_global(
+                    matches,
+                    key,
+                    merged.and_then(|merged| merged.#name.as_ref()),
+                    self.__sections.get_mut(#section_key),
+                ),
+                None => Ok(None),
+            },
+        }
+    });
+    let prepare_arms = variants.iter().map(|(kebab_case_name, name, ty)| {
+        let section_key = name.to_string();
+        let apply_defaults = container_attrs.defaults_key.as_ref().map(|(key, _)| quote! {
+            if let Some(defaults) = self.__defaults.take() {
+                self.__sections.insert_defaults(#section_key, &defaults);
+                let defaults: #ty = ::clap_config::__private::section_defaults(#key, defaults)?;
+                self.#name = ::clap_config::__private::merge_nested(Some(defaults), self.#name.take(), #ty::merge);
+            }
+        });
+        quote! {
+            #kebab_case_name => {
+                #apply_defaults
+                if let Some(config) = self.#name.as_mut() {
+                    config.__prepare_layer(matches, merged.and_then(|merged| merged.#name.as_ref()))?;
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __take_global(
+            &mut self,
+            subcommand_name: &str,
+            matches: &clap::ArgMatches,
+            key: &str,
+            merged: ::std::option::Option<&Self>,
+        ) -> ::std::result::Result<::std::option::Option<::clap_config::__private::Value>, clap::Error> {
+            match subcommand_name {
+                #(#take_arms)*
+                _ => Ok(None),
+            }
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        pub fn __prepare_layer(
+            &mut self,
+            subcommand_name: &str,
+            matches: &clap::ArgMatches,
+            merged: ::std::option::Option<&Self>,
+        ) -> ::std::result::Result<(), clap::Error> {
+            match subcommand_name {
+                #(#prepare_arms)*
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+}
+
+/**
+Generate subcommand merging method that merges our config into the clap-generated enum, with precedence being:
+
+- Things specified via `--arg` or `$ENV_VAR`
+- Things in the config
+- Clap defaults
+*/
+fn enum_merge_method(config_ident: &Ident, variants: &Punctuated<Variant, Comma>) -> TokenStream {
+    let match_arms = variants.iter().map(|v| {
+        let name = &v.ident;
+        // TODO(gib): handle non-standard formats.
+        let kebab_case_name = &name.to_string().as_str().to_kebab_case();
+        let snake_case_ident = Ident::new(&name.to_string().as_str().to_snake_case(), name.span());
+        let Some(f) = get_variant_field(v) else {
+            // Unit variant has no fields, so just return it.
+            return quote!(#kebab_case_name => Self::#name,);
+        };
+        let ty = &f.ty;
+
+        let subcmd_opts_name = &ty;
+
+        let key = snake_case_ident.to_string();
+        quote! {
+            #kebab_case_name => {
+                sources.push(#key);
+                let merged = #subcmd_opts_name::__try_from_merged(Some(#kebab_case_name), matches,
+                    config.and_then(|c| c.#snake_case_ident), sources)?;
+                sources.pop();
+                Self::#name(merged)
+            }
+        }
+    });
+
+    quote! {
+        /// Merge the values from the command line and the config for the subcommand
+        /// `subcommand_name`, exiting with a clap error if they are invalid.
+        pub fn from_merged(
+            subcommand_name: String,
+            matches: clap::ArgMatches,
+            config: ::std::option::Option<#config_ident>
+        ) -> Self {
+            Self::try_from_merged(subcommand_name, matches, config).unwrap_or_else(|e| e.exit())
+        }
+
+        /// Merge the values from the command line and the config for the subcommand
+        /// `subcommand_name`.
+        pub fn try_from_merged(
+            subcommand_name: String,
+            matches: clap::ArgMatches,
+            mut config: ::std::option::Option<#config_ident>
+        ) -> ::std::result::Result<Self, clap::Error> {
+            let merged = config.clone();
+            if let Some(config) = config.as_mut() {
+                config.__prepare_layer(&subcommand_name, &matches, merged.as_ref())?;
+            }
+            Self::__try_from_merged(subcommand_name, matches, config, &mut ::std::default::Default::default())
+        }
+
+        #[doc(hidden)]
+        pub fn __try_from_merged(
+            subcommand_name: String,
+            mut matches: clap::ArgMatches,
+            mut config: ::std::option::Option<#config_ident>,
+            sources: &mut ::clap_config::__private::SourceRecorder,
+        ) -> ::std::result::Result<Self, clap::Error> {
+            Ok(match subcommand_name.as_str() {
+                #(#match_arms)*
+                _ => unimplemented!("Should have exhaustively checked all possible subcommands."),
+            })
+        }
+    }
+}
+
+/**
+Generate the body of the `From<&Opts>` impl of a config struct, which converts each merged value
+back into its config value.
+
+Parsed fields are converted back to their raw strings, with the `ValueEnum` name for `value_enum`
+fields and `Display` otherwise.
+*/
+fn struct_from_impl_body(
+    container_attrs: &ContainerAttrs,
+    fields: &Punctuated<Field, Comma>,
+) -> TokenStream {
+    let field_values = fields.iter().filter_map(|f| {
+        let name = &f.ident;
+        match is_field_configurable(container_attrs, f) {
+            Ok(true) => (),
+            Ok(false) => return None,
+            Err(e) => return Some(e.into_compile_error()),
+        }
+
+        let stripped_ty = strip_optional_wrapper_if_present(f);
+        if is_subcommand_field(f).expect("Failed to check if field is subcommand.") {
+            let ty = make_subcommand_ty(stripped_ty.unwrap_or(&f.ty));
+            return Some(if stripped_ty.is_some() {
+                quote!(#name: opts.#name.as_ref().map(#ty::from))
+            } else {
+                quote!(#name: Some(#ty::from(&opts.#name)))
+            });
+        }
+
+        // Converts a reference to the value to the config value, or to an `Option` of it if the
+        // conversion is `fallible`.
+        let value_ty = stripped_ty.unwrap_or(&f.ty);
+        let (convert, fallible) = match is_field_parsed(f) {
+            Ok(true) if has_value_enum(f).unwrap_or_default() => {
+                let raw = quote!(::clap_config::__private::value_enum_name);
+                if strip_vec_wrapper_if_present(f).is_some() {
+                    (quote!(|value: &#value_ty| value.iter().map(#raw).collect::<::std::vec::Vec<_>>()), false)
+                } else {
+                    (raw, false)
+                }
+            }
+            // The type of a custom value parser doesn't have to implement any trait, so values
+            // that can't be converted back into a string are left out, see `ToRaw`.
+            Ok(true) => {
+                let elem_ty = strip_vec_wrapper_if_present(f).unwrap_or(value_ty);
+                let raw = quote! {
+                    |value: &#elem_ty| {
+                        #[allow(unused_imports)]
+                        use ::clap_config::__private::{
+                            DisplayToRaw as _, NoToRaw as _, SerializeToRaw as _, ValueEnumToRaw as _,
+                        };
+                        (&&&::clap_config::__private::ToRaw(value)).to_raw()
+                    }
+                };
+                if strip_vec_wrapper_if_present(f).is_some() {
+                    (quote!(|value: &#value_ty| value.iter().map(#raw).collect::<::std::option::Option<::std::vec::Vec<_>>>()), true)
+                } else {
+                    (raw, true)
+                }
+            }
+            Ok(false) if is_vec_tuple_string(f) => {
+                (quote!(|value: &#value_ty| value.iter().cloned().collect::<::std::collections::BTreeMap<_, _>>()), false)
+            }
+            Ok(false) => (quote!(::std::clone::Clone::clone), false),
+            Err(e) => return Some(e.into_compile_error()),
+        };
+        let wrap = if is_field_lockable(container_attrs, f).unwrap_or_default() {
+            quote!(.map(::clap_config::Lockable::Value))
+        } else if is_field_nullable(container_attrs, f).unwrap_or_default() {
+            quote!(.map(Some))
+        } else {
+            quote!()
+        };
+        Some(match (stripped_ty.is_some(), fallible) {
+            (true, false) => quote!(#name: opts.#name.as_ref().map(#convert) #wrap),
+            (true, true) => quote!(#name: opts.#name.as_ref().and_then(#convert) #wrap),
+            (false, false) => quote!(#name: Some((#convert)(&opts.#name)) #wrap),
+            (false, true) => quote!(#name: (#convert)(&opts.#name) #wrap),
+        })
+    });
+
+    let aliases = container_attrs.aliases_key.is_some().then(|| quote!(__aliases: None,));
+    let args = container_attrs.args_key.is_some().then(|| quote!(__args: None,));
+
+    quote! {
+        Self {
+            #(#field_values,)*
+            #aliases
+            #args
+        }
+    }
+}
+
+/// Generate the body of the `From<&SubCommand>` impl of a subcommand config, which sets the
+/// config of the selected subcommand.
+fn enum_from_impl_body(input_ident: &Ident, variants: &Punctuated<Variant, Comma>) -> TokenStream {
+    let match_arms = variants.iter().map(|v| {
+        let variant = &v.ident;
+        let name = Ident::new(&v.ident.to_string().as_str().to_snake_case(), v.ident.span());
+        let Some(f) = get_variant_field(v) else {
+            return quote!(#input_ident::#variant => Self::default(),);
+        };
+        let ty = make_subcommand_ty(&f.ty);
+        let pattern = match &f.ident {
+            Some(field) => quote!(#input_ident::#variant { #field: opts }),
+            None => quote!(#input_ident::#variant(opts)),
+        };
+        quote! {
+            #pattern => Self {
+                #name: Some(#ty::from(opts)),
+                // The other subcommand configs are left unset.
+                ..Self::default()
+            },
+        }
+    });
+
+    quote! {
+        match opts {
+            #(#match_arms)*
+        }
+    }
+}
+
+/**
+Generate a method that relaxes the required args, `requires`, and required group rules of the clap
+command, so that clap doesn't reject command lines where they are only satisfied by the config.
+
+`requires_if` and `requires_ifs` rules are kept, as we can't check them after merging.
+*/
+fn struct_relax_method(container_attrs: &ContainerAttrs, fields: &Punctuated<Field, Comma>) -> TokenStream {
+    let config_names = fields
+        .iter()
+        .filter(|f| {
+            is_field_configurable(container_attrs, f).unwrap_or(false)
+                && !is_subcommand_field(f).unwrap_or(false)
+        })
+        .map(|f| {
+            let name_str = f.ident.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+            name_str.strip_prefix("r#").unwrap_or(&name_str).to_owned()
+        });
+    let arg_updates = fields.iter().map(|f| {
+        let ty = &f.ty;
+        let name_str = f.ident.as_ref().map(|name| name.to_string()).expect("Expected field to have a name");
+        let name_str = name_str.strip_prefix("r#").unwrap_or(&name_str);
+
+        if is_subcommand_field(f).expect("Failed to check if field is subcommand.") {
+            let stripped_ty = strip_optional_wrapper_if_present(f).unwrap_or(ty);
+            return quote!(let cmd = #stripped_ty::relax_command(cmd););
+        }
+        match (requires_exprs(f), conditional_requires_calls(f)) {
+            (Ok(exprs), _) if exprs.is_empty() => quote!(),
+            (Ok(_), Ok(calls)) => quote! {
+                let cmd = cmd.mut_arg(#name_str, |arg| arg.requires(clap::builder::Resettable::<clap::Id>::Reset) #(#calls)*);
+            },
+            (Err(e), _) | (_, Err(e)) => e.into_compile_error(),
+        }
+    });
+
+    quote! {
+        /// Relax the required args, `requires`, and required group rules of `cmd` (the command
+        /// for `Self`), so that they can be satisfied by the config. They are checked after
+        /// merging instead.
+        pub fn relax_command(cmd: clap::Command) -> clap::Command {
+            let cmd = ::clap_config::__private::relax_groups(cmd);
+            let cmd = ::clap_config::__private::relax_required(cmd, &[#(#config_names),*]);
+            #(#arg_updates)*
+            cmd
+        }
+    }
+}
+
+/// Generate a method that relaxes the command for each subcommand, see [`struct_relax_method`].
+fn enum_relax_method(variants: &Punctuated<Variant, Comma>) -> TokenStream {
+    let subcommand_updates = variants.iter().filter_map(|v| {
+        let kebab_case_name = v.ident.to_string().as_str().to_kebab_case();
+        let ty = &get_variant_field(v)?.ty;
+        Some(quote!(let cmd = cmd.mut_subcommand(#kebab_case_name, #ty::relax_command);))
+    });
+
+    quote! {
+        /// Relax the `requires` and required group rules of the subcommands in `cmd`, so that
+        /// they can be satisfied by the config. They are checked after merging instead.
+        pub fn relax_command(cmd: clap::Command) -> clap::Command {
+            #(#subcommand_updates)*
+            cmd
+        }
+    }
+}
+
+// TODO(gib): steal from
+// <https://stackoverflow.com/questions/55271857/how-can-i-get-the-t-from-an-optiont-when-using-syn>
+// ?
+/// If the field type is `Option<Foo>`, return `Some(Foo)`. Else return `None`.
+#[allow(clippy::collapsible_if)]
+fn strip_optional_wrapper_if_present(f: &Field) -> Option<&Type> {
+    let ty = &f.ty;
+    if let Type::Path(TypePath { path, .. }) = ty {
+        if let Some(PathSegment { ident, arguments }) = path.segments.last() {
+            if ident == &Ident::new("Option", f.span()) {
+                if let PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                    args, ..
+                }) = arguments
+                {
+                    if let Some(GenericArgument::Type(inner_type)) = args.first() {
+                        return Some(inner_type);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// If the field type is `Vec<(String, String)>`, return `true`. Else return `false`.
+#[allow(clippy::collapsible_if)]
+fn is_vec_tuple_string(f: &Field) -> bool {
+    let ty = &f.ty;
+    fn path_is_ident(elem: Option<&Type>, ident: &Ident) -> bool {
+        let Some(elem) = elem else { return false };
+        if let Type::Path(TypePath { path, .. }) = elem {
+            return path.is_ident(ident);
+        }
+        false
+    }
+
+    if let Type::Path(TypePath { path, .. }) = ty {
+        if let Some(PathSegment { ident, arguments }) = path.segments.last() {
+            if ident == &Ident::new("Vec", f.span()) {
+                if let PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                    args, ..
+                }) = arguments
+                {
+                    if let Some(GenericArgument::Type(Type::Tuple(TypeTuple { elems, .. }))) =
+                        args.first()
+                    {
+                        let string_ident = Ident::new("String", f.span());
+                        if path_is_ident(elems.first(), &string_ident)
+                            && path_is_ident(elems.last(), &string_ident)
+                        {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// If the field type is `Vec<(String, String)>` and it has a custom value parser, return `true`, as
+/// each `key: value` config entry should be validated by parsing it as `key=value`.
+fn is_vec_tuple_string_validated(f: &Field) -> Result<bool, syn::Error> {
+    Ok(is_vec_tuple_string(f) && has_value_validation(f)?)
+}
+
+/// If the field type is `Vec<Foo>`, return `Some(Foo)`. Else return `None`.
+#[allow(clippy::collapsible_if)]
+fn strip_vec_wrapper_if_present(f: &Field) -> Option<&Type> {
+    let ty = &f.ty;
+
+    if let Type::Path(TypePath { path, .. }) = ty {
+        if let Some(PathSegment { ident, arguments }) = path.segments.last() {
+            if ident == &Ident::new("Vec", f.span()) {
+                if let PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                    args, ..
+                }) = arguments
+                {
+                    if let Some(GenericArgument::Type(inner_type)) = args.first() {
+                        return Some(inner_type);
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Returns whether the field has a field attribute `#[clap(subcommand)]`.
+fn is_subcommand_field(f: &Field) -> Result<bool, syn::Error> {
+    Ok(clap_attr_metas(f)?
+        .iter()
+        .any(|meta| matches!(meta, Meta::Path(path) if path.is_ident("subcommand"))))
+}
+
+// Returns whether the field has a field attribute `#[clap(value_enum)]`.
+fn has_value_enum(f: &Field) -> Result<bool, syn::Error> {
+    Ok(clap_attr_metas(f)?
+        .iter()
+        .any(|meta| matches!(meta, Meta::Path(path) if path.is_ident("value_enum"))))
+}
+
+/**
+Returns whether the field's clap attributes declare validation beyond the default parser for its
+type, i.e. `value_enum` or a custom `value_parser = ...`.
+
+Config values for these fields have to go through the same value parser as the command line.
+*/
+fn has_value_validation(f: &Field) -> Result<bool, syn::Error> {
+    Ok(clap_attr_metas(f)?.iter().any(|meta| match meta {
+        Meta::Path(path) => path.is_ident("value_enum"),
+        Meta::NameValue(name_value) => name_value.path.is_ident("value_parser"),
+        Meta::List(_) => false,
+    }))
+}
+
+/**
+The field's `default_value_if` and `default_value_ifs` clap attributes, as statements that push
+each `(arg id, predicate, default)` condition onto a `conditions` vec.
+*/
+fn conditional_default_exprs(f: &Field) -> Result<Vec<TokenStream>, syn::Error> {
+    let mut conditions = vec![];
+    for meta in clap_attr_metas(f)? {
+        let (path, args) = match meta {
+            Meta::List(list) => {
+                let args = list.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+                (list.path, args.into_iter().collect::<Vec<_>>())
+            }
+            Meta::NameValue(name_value) => (name_value.path, vec![name_value.value]),
+            Meta::Path(_) => continue,
+        };
+        if path.is_ident("default_value_if") {
+            let [arg, predicate, default] = args.as_slice() else {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    "expected `default_value_if(arg, predicate, default)`",
+                ));
+            };
+            conditions.push(quote! {
+                conditions.push((
+                    clap::Id::from(#arg),
+                    ::std::convert::Into::<clap::builder::ArgPredicate>::into(#predicate),
+                    clap::builder::IntoResettable::<clap::builder::OsStr>::into_resettable(#default),
+                ));
+            });
+        } else if path.is_ident("default_value_ifs") {
+            let [ifs] = args.as_slice() else {
+                return Err(syn::Error::new_spanned(path, "expected `default_value_ifs(ifs)`"));
+            };
+            conditions.push(quote! {
+                conditions.extend(::std::iter::IntoIterator::into_iter(#ifs).map(|(arg, predicate, default)| (
+                    ::std::convert::Into::<clap::Id>::into(arg),
+                    ::std::convert::Into::<clap::builder::ArgPredicate>::into(predicate),
+                    clap::builder::IntoResettable::<clap::builder::OsStr>::into_resettable(default),
+                )));
+            });
+        }
+    }
+    Ok(conditions)
+}
+
+/// The ids passed to `requires = ...` or `requires(...)` in the field's clap attributes.
+fn requires_exprs(f: &Field) -> Result<Vec<TokenStream>, syn::Error> {
+    Ok(clap_attr_metas(f)?
+        .into_iter()
+        .filter_map(|meta| match meta {
+            Meta::NameValue(name_value) if name_value.path.is_ident("requires") => {
+                let value = name_value.value;
+                Some(quote!(#value))
+            }
+            Meta::List(list) if list.path.is_ident("requires") => Some(list.tokens),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The field's `requires_if` and `requires_ifs` clap attributes, as builder method calls.
+fn conditional_requires_calls(f: &Field) -> Result<Vec<TokenStream>, syn::Error> {
+    Ok(clap_attr_metas(f)?
+        .into_iter()
+        .filter_map(|meta| match meta {
+            Meta::NameValue(name_value)
+                if name_value.path.is_ident("requires_if")
+                    || name_value.path.is_ident("requires_ifs") =>
+            {
+                let (path, value) = (name_value.path, name_value.value);
+                Some(quote!(.#path(#value)))
+            }
+            Meta::List(list)
+                if list.path.is_ident("requires_if") || list.path.is_ident("requires_ifs") =>
+            {
+                let (path, tokens) = (list.path, list.tokens);
+                Some(quote!(.#path(#tokens)))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// Whether the field is a global arg, with `#[arg(global = true)]`.
+fn is_field_global(f: &Field) -> Result<bool, syn::Error> {
+    Ok(clap_attr_metas(f)?.into_iter().any(|meta| match meta {
+        Meta::NameValue(name_value) if name_value.path.is_ident("global") => matches!(
+            name_value.value,
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Bool(syn::LitBool { value: true, .. }), .. })
+        ),
+        _ => false,
+    }))
+}
+
+/// All the items in the field's `#[clap(...)]`, `#[arg(...)]`, and `#[command(...)]` attributes.
+fn clap_attr_metas(f: &Field) -> Result<Vec<Meta>, syn::Error> {
+    let mut metas = vec![];
+    for attr in f.attrs.iter() {
+        if ["clap", "arg", "command"]
+            .iter()
+            .any(|name| attr.path().is_ident(name))
+        {
+            metas.extend(attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?);
+        }
+    }
+    Ok(metas)
+}
+
+/// Options set on the struct or enum itself with `#[clap_config(...)]`.
+#[derive(Default)]
+struct ContainerAttrs {
+    /// Set by `#[clap_config(opt_in)]`: only fields marked `#[clap_config(include)]` are
+    /// configurable.
+    opt_in: Option<proc_macro2::Span>,
+    /// Set by `#[clap_config(merge = "...")]`: the default merge strategy for `Vec` fields.
+    merge: Option<(MergeStrategy, proc_macro2::Span)>,
+    /// Set by `#[clap_config(precedence = "...")]`: the default precedence for all fields.
+    precedence: Option<(Precedence, proc_macro2::Span)>,
+    /// Set by `#[clap_config(lockable)]`: all fields can be locked in the config.
+    lockable: Option<proc_macro2::Span>,
+    /// Set by `#[clap_config(nullable)]`: all fields can be reset with `null` in the config.
+    nullable: Option<proc_macro2::Span>,
+    /// Set by `#[clap_config(subcommands_key)]` or `#[clap_config(subcommands_key = "...")]`:
+    /// the key to nest the subcommand configs under, instead of flattening them.
+    subcommands_key: Option<(String, proc_macro2::Span)>,
+    /// Set by `#[clap_config(defaults_key)]` or `#[clap_config(defaults_key = "...")]` on a
+    /// subcommand enum: the key of a section with defaults for every subcommand.
+    defaults_key: Option<(String, proc_macro2::Span)>,
+    /// Set by `#[clap_config(default_command_key)]` or
+    /// `#[clap_config(default_command_key = "...")]` on a subcommand enum: the key of the
+    /// subcommand to run when none is given.
+    default_command_key: Option<(String, proc_macro2::Span)>,
+    /// Set by `#[clap_config(aliases_key)]` or `#[clap_config(aliases_key = "...")]` on a
+    /// struct: the key of a section with command aliases.
+    aliases_key: Option<(String, proc_macro2::Span)>,
+    /// Set by `#[clap_config(args_key)]` or `#[clap_config(args_key = "...")]` on a struct: the
+    /// key of a list of extra command line args.
+    args_key: Option<(String, proc_macro2::Span)>,
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self, syn::Error> {
+        let mut container_attrs = Self::default();
+        for attr in attrs {
+            if !attr.path().is_ident(CLAP_CONFIG_ATTR_NAME) {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("opt_in") {
+                    container_attrs.opt_in = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("merge") {
+                    container_attrs.merge = Some((MergeStrategy::parse(&meta)?, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("precedence") {
+                    container_attrs.precedence = Some((Precedence::parse(&meta)?, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("lockable") {
+                    container_attrs.lockable = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("nullable") {
+                    container_attrs.nullable = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("subcommands_key") {
+                    let key = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?.value()
+                    } else {
+                        DEFAULT_SUBCOMMANDS_KEY.to_owned()
+                    };
+                    container_attrs.subcommands_key = Some((key, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("defaults_key") {
+                    let key = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?.value()
+                    } else {
+                        DEFAULT_DEFAULTS_KEY.to_owned()
+                    };
+                    container_attrs.defaults_key = Some((key, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("default_command_key") {
+                    let key = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?.value()
+                    } else {
+                        DEFAULT_DEFAULT_COMMAND_KEY.to_owned()
+                    };
+                    container_attrs.default_command_key = Some((key, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("aliases_key") {
+                    let key = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?.value()
+                    } else {
+                        DEFAULT_ALIASES_KEY.to_owned()
+                    };
+                    container_attrs.aliases_key = Some((key, meta.path.span()));
+                    Ok(())
+                } else if meta.path.is_ident("args_key") {
+                    let key = if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?.value()
+                    } else {
+                        DEFAULT_ARGS_KEY.to_owned()
+                    };
+                    container_attrs.args_key = Some((key, meta.path.span()));
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown `{CLAP_CONFIG_ATTR_NAME}` container attribute, expected one of \
+                         `opt_in`, `merge`, `precedence`, `lockable`, `nullable`, `subcommands_key`, \
+                         `defaults_key`, `default_command_key`, `aliases_key`, `args_key`"
+                    )))
+                }
+            })?;
+        }
+        Ok(container_attrs)
+    }
+}
+
+/// Options set on a field with `#[clap_config(...)]`.
+#[derive(Default)]
+struct FieldAttrs {
+    /// Set by `#[clap_config(skip)]`: never read this field from the config.
+    skip: bool,
+    /// Set by `#[clap_config(include)]`: read this field from the config in `opt_in` mode.
+    include: bool,
+    /// Set by `#[clap_config(parse)]`: store the raw string in the config, and parse it with the
+    /// arg's clap value parser when merging.
+    parse: bool,
+    /// Set by `#[clap_config(merge = "...")]`: how to merge the command line and config values.
+    merge: Option<MergeStrategy>,
+    /// Set by `#[clap_config(precedence = "...")]`: which sources the config value beats.
+    precedence: Option<Precedence>,
+    /// Set by `#[clap_config(lockable)]`: the config value can be locked, see
+    /// `clap_config::Lockable`.
+    lockable: bool,
+    /// Set by `#[clap_config(nullable)]`: `null` in the config resets the value to the clap
+    /// default, instead of being the same as a missing key.
+    nullable: bool,
+}
+
+impl FieldAttrs {
+    fn parse(f: &Field) -> Result<Self, syn::Error> {
+        let mut field_attrs = Self::default();
+        for attr in f.attrs.iter() {
+            if !attr.path().is_ident(CLAP_CONFIG_ATTR_NAME) {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    field_attrs.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("include") {
+                    field_attrs.include = true;
+                    Ok(())
+                } else if meta.path.is_ident("parse") {
+                    field_attrs.parse = true;
+                    Ok(())
+                } else if meta.path.is_ident("merge") {
+                    field_attrs.merge = Some(MergeStrategy::parse(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("precedence") {
+                    field_attrs.precedence = Some(Precedence::parse(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("lockable") {
+                    field_attrs.lockable = true;
+                    Ok(())
+                } else if meta.path.is_ident("nullable") {
+                    field_attrs.nullable = true;
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown `{CLAP_CONFIG_ATTR_NAME}` field attribute, expected one of \
+                         `skip`, `include`, `parse`, `merge`, `precedence`, `lockable`, `nullable`"
+                    )))
+                }
+            })?;
+        }
+        Ok(field_attrs)
+    }
+}
+
+/// How to merge a value set on the command line with the same value set in the config.
+#[derive(Clone, Copy, PartialEq)]
+enum MergeStrategy {
+    /// Use the command line value, ignoring the config (the default).
+    Replace,
+    /// Add the command line values after the config values.
+    Append,
+    /// Add the command line values before the config values.
+    Prepend,
+    /// Use the config map as the base, with the command line `key=value` pairs overriding single
+    /// keys, and `key=` removing them.
+    Deep,
+}
+
+impl MergeStrategy {
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> Result<Self, syn::Error> {
+        let value: syn::LitStr = meta.value()?.parse()?;
+        match value.value().as_str() {
+            "replace" => Ok(Self::Replace),
+            "append" => Ok(Self::Append),
+            "prepend" => Ok(Self::Prepend),
+            "deep" => Ok(Self::Deep),
+            _ => Err(syn::Error::new_spanned(
+                value,
+                "unknown merge strategy, expected one of `replace`, `append`, `prepend`, `deep`",
+            )),
+        }
+    }
+}
+
+/**
+The order in which values from the command line, env vars, the config, and clap defaults take
+precedence, e.g. `#[clap_config(precedence = "cli, config, env, default")]` to let the config
+beat env vars.
+
+Clap resolves the command line, env vars and defaults itself, so only the position of the config
+can change.
+*/
+#[derive(Clone)]
+struct Precedence {
+    /// The clap `ValueSource` variants that a config value takes precedence over.
+    config_beats: Vec<Ident>,
+}
+
+impl Default for Precedence {
+    fn default() -> Self {
+        Self {
+            config_beats: vec![format_ident!("DefaultValue")],
+        }
+    }
+}
+
+impl Precedence {
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> Result<Self, syn::Error> {
+        let value: syn::LitStr = meta.value()?.parse()?;
+        let sources: Vec<String> = value
+            .value()
+            .split(',')
+            .map(|source| source.trim().to_owned())
+            .collect();
+
+        let mut sorted = sources.clone();
+        sorted.sort();
+        if sorted != ["cli", "config", "default", "env"] {
+            return Err(syn::Error::new_spanned(
+                value,
+                "expected `precedence` to list each of `cli`, `env`, `config`, `default` once, \
+                 highest precedence first",
+            ));
+        }
+        let clap_sources: Vec<&String> = sources.iter().filter(|source| *source != "config").collect();
+        if clap_sources != ["cli", "env", "default"] {
+            return Err(syn::Error::new_spanned(
+                value,
+                "`cli`, `env` and `default` have to stay in that order, as clap resolves them, \
+                 only the position of `config` can change",
+            ));
+        }
+
+        let config_position = sources.iter().position(|source| source == "config").expect("checked above");
+        let config_beats = sources[config_position + 1..]
+            .iter()
+            .map(|source| match source.as_str() {
+                "cli" => format_ident!("CommandLine"),
+                "env" => format_ident!("EnvVariable"),
+                _ => format_ident!("DefaultValue"),
+            })
+            .collect();
+        Ok(Self { config_beats })
+    }
+
+    /// An expression that is true if a config value beats the `value_source` of the clap value.
+    fn config_wins(&self) -> TokenStream {
+        if self.config_beats.is_empty() {
+            // Still use `value_source` to avoid an unused variable warning.
+            return quote!({
+                let _ = value_source;
+                false
+            });
+        }
+        let config_beats = &self.config_beats;
+        quote!(matches!(value_source, #(clap::parser::ValueSource::#config_beats)|*))
+    }
+}
+
+/// Get the precedence for this field, from `#[clap_config(precedence = "...")]` on the field or the
+/// struct.
+fn field_precedence(container_attrs: &ContainerAttrs, f: &Field) -> Result<Precedence, syn::Error> {
+    Ok(FieldAttrs::parse(f)?
+        .precedence
+        .or_else(|| container_attrs.precedence.clone().map(|(precedence, _)| precedence))
+        .unwrap_or_default())
+}
+
+/**
+Get the merge strategy for this field, from `#[clap_config(merge = "...")]` on the field or the
+struct.
+
+`append` and `prepend` only apply to `Vec` fields, and `deep` only to `Vec<(String, String)>` map
+fields, so the struct-level strategy is ignored for fields it doesn't apply to.
+*/
+fn field_merge_strategy(container_attrs: &ContainerAttrs, f: &Field) -> Result<MergeStrategy, syn::Error> {
+    let is_map = is_vec_tuple_string(f);
+    let is_vec = strip_vec_wrapper_if_present(f).is_some() && !is_map;
+    let is_supported = |merge: &MergeStrategy| match merge {
+        MergeStrategy::Replace => is_vec || is_map,
+        MergeStrategy::Append | MergeStrategy::Prepend => is_vec,
+        MergeStrategy::Deep => is_map,
+    };
+    match FieldAttrs::parse(f)?.merge {
+        Some(merge) if is_supported(&merge) => Ok(merge),
+        Some(MergeStrategy::Deep) => Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(merge = \"deep\")` is only supported on `Vec<(String, String)>` fields",
+        )),
+        Some(_) => Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(merge)` is only supported on `Vec` fields",
+        )),
+        None => Ok(container_attrs
+            .merge
+            .map(|(merge, _)| merge)
+            .filter(is_supported)
+            .unwrap_or(MergeStrategy::Replace)),
+    }
+}
+
+/**
+Check whether this field should be generated in the config struct and read from it when merging.
+
+Fields are configurable unless marked `#[clap_config(skip)]`, or, if the container is marked
+`#[clap_config(opt_in)]`, unless they are marked `#[clap_config(include)]`.
+*/
+fn is_field_configurable(container_attrs: &ContainerAttrs, f: &Field) -> Result<bool, syn::Error> {
+    let field_attrs = FieldAttrs::parse(f)?;
+    if field_attrs.skip && field_attrs.include {
+        return Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(skip)` and `clap_config(include)` can't be used together",
+        ));
+    }
+    if field_attrs.include && container_attrs.opt_in.is_none() {
+        return Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(include)` requires `#[clap_config(opt_in)]` on the struct",
+        ));
+    }
+
+    if container_attrs.opt_in.is_some() {
+        Ok(field_attrs.include)
+    } else {
+        Ok(!field_attrs.skip)
+    }
+}
+
+/**
+Check whether the config value for this field can be locked, so that it wins over the command line
+and env vars, either because the field or the struct is marked `#[clap_config(lockable)]`.
+
+The struct-level attribute only applies to fields that are read from the config and aren't
+subcommands.
+*/
+fn is_field_lockable(container_attrs: &ContainerAttrs, f: &Field) -> Result<bool, syn::Error> {
+    let is_configurable = is_field_configurable(container_attrs, f)?;
+    let is_subcommand = is_subcommand_field(f)?;
+    if FieldAttrs::parse(f)?.lockable {
+        if !is_configurable || is_subcommand {
+            return Err(syn::Error::new_spanned(
+                f,
+                "`clap_config(lockable)` is only supported on fields that are read from the config, \
+                 and not on subcommands",
+            ));
+        }
+        return Ok(true);
+    }
+    Ok(container_attrs.lockable.is_some() && is_configurable && !is_subcommand)
+}
+
+/**
+Check whether an explicit `null` for this field in the config resets it to the clap default, either
+because the field or the struct is marked `#[clap_config(nullable)]`.
+
+This matters when merging config layers, where a missing key keeps the value from a lower layer.
+The struct-level attribute only applies to fields that are read from the config, and aren't
+subcommands or lockable.
+*/
+fn is_field_nullable(container_attrs: &ContainerAttrs, f: &Field) -> Result<bool, syn::Error> {
+    let is_configurable = is_field_configurable(container_attrs, f)?;
+    let is_subcommand = is_subcommand_field(f)?;
+    let is_lockable = is_field_lockable(container_attrs, f)?;
+    if FieldAttrs::parse(f)?.nullable {
+        if !is_configurable || is_subcommand {
+            return Err(syn::Error::new_spanned(
+                f,
+                "`clap_config(nullable)` is only supported on fields that are read from the config, \
+                 and not on subcommands",
+            ));
+        }
+        if is_lockable {
+            return Err(syn::Error::new_spanned(
+                f,
+                "`clap_config(nullable)` and `clap_config(lockable)` can't be used together",
+            ));
+        }
+        return Ok(true);
+    }
+    Ok(container_attrs.nullable.is_some() && is_configurable && !is_subcommand && !is_lockable)
+}
+
+/**
+Check whether the config value for this field should be stored as a string and parsed with the
+clap value parser, either because the user asked for it with `#[clap_config(parse)]`, or because
+the arg has its own validation that the config value also has to pass.
+
+`Vec<(String, String)>` fields are always stored as maps, see [`is_vec_tuple_string_validated`].
+*/
+fn is_field_parsed(f: &Field) -> Result<bool, syn::Error> {
+    let field_attrs = FieldAttrs::parse(f)?;
+    if !field_attrs.parse {
+        return Ok(!is_vec_tuple_string(f) && has_value_validation(f)?);
+    }
+    if field_attrs.skip {
+        return Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(skip)` and `clap_config(parse)` can't be used together",
+        ));
+    }
+    if is_subcommand_field(f)? || is_vec_tuple_string(f) {
+        return Err(syn::Error::new_spanned(
+            f,
+            "`clap_config(parse)` isn't supported on subcommand or `Vec<(String, String)>` fields",
+        ));
+    }
+    Ok(true)
+}